@@ -0,0 +1,119 @@
+// src/bake.rs
+use raylib::prelude::*;
+
+use crate::bump::bumped_normal;
+use crate::fragment::Fragment;
+use crate::shaders::{fragment_albedo, relief_height, Material};
+use crate::texture::equirect_uv;
+use crate::uniforms::{vec3_to_color, Uniforms};
+
+/// Equirectangular maps of a spherical body: rows top (north pole) to bottom, columns by longitude,
+/// laid out like `texture::equirect_uv` so they load back as `UvMapping::Equirect` maps.
+pub struct BakedMaps {
+    pub width: u32,
+    pub height: u32,
+    pub albedo: Vec<Vector3>,
+    pub relief: Vec<f32>,     // object-space height above the base sphere
+    pub normal: Vec<Vector3>, // tangent space (x east, y north, z up), encoded 0..1
+}
+
+/// Object-space direction at the center of texel (x, y)
+fn texel_dir(x: f32, y: f32, width: u32, height: u32) -> Vector3 {
+    let theta = x / width as f32 * std::f32::consts::TAU;
+    let phi = y / height as f32 * std::f32::consts::PI;
+    Vector3::new(phi.sin() * theta.cos(), phi.cos(), phi.sin() * theta.sin())
+}
+
+/// Evaluates `mat` (its enabled layers, unlit) over a sphere of `radius` at `time`.
+/// `mesh_height` is the vertex stage displacement at an object-space point; the fragment
+/// stage relief of the material is added to it for the height and normal maps.
+pub fn bake_sphere(
+    mat: &Material,
+    radius: f32,
+    width: u32,
+    height: u32,
+    time: f32,
+    mesh_height: impl Fn(Vector3) -> f32,
+) -> BakedMaps {
+    let total_height = |p: Vector3| mesh_height(p) + relief_height(p, mat);
+    let epsilon = radius * 0.002;
+    let size = (width * height) as usize;
+    let mut maps = BakedMaps {
+        width,
+        height,
+        albedo: Vec::with_capacity(size),
+        relief: Vec::with_capacity(size),
+        normal: Vec::with_capacity(size),
+    };
+
+    for y in 0..height {
+        for x in 0..width {
+            let (fx, fy) = (x as f32 + 0.5, y as f32 + 0.5);
+            let dir = texel_dir(fx, fy, width, height);
+            let p = dir * radius;
+
+            // Seen straight down the normal, so view-dependent terms (limb darkening) drop out
+            let u = Uniforms {
+                time,
+                resolution: Vector2::new(width as f32, height as f32),
+                camera_pos: dir * (radius * 3.0),
+                light_pos: dir * (radius * 100.0),
+                light_color: Vector3::one(),
+                light_dir_obj: dir,
+                model: Matrix::identity(),
+            };
+            let mut fragment = Fragment::new(fx, fy, Vector3::one(), 0.0);
+            fragment.obj_pos = p;
+            fragment.world_pos = p;
+            fragment.normal = dir;
+            fragment.uv = equirect_uv(p);
+            fragment.dp_dx = texel_dir(fx + 1.0, fy, width, height) * radius - p;
+            fragment.dp_dy = texel_dir(fx, fy + 1.0, width, height) * radius - p;
+            maps.albedo.push(fragment_albedo(&fragment, &u, mat));
+
+            maps.relief.push(total_height(p));
+
+            // Object-space bumped normal, expressed in the texel's east/north/up frame
+            let n = bumped_normal(p, dir, 1.0, epsilon, total_height);
+            let east = Vector3::new(-dir.z, 0.0, dir.x).normalized();
+            let north = east.cross(dir);
+            let t = Vector3::new(n.dot(east), n.dot(north), n.dot(dir));
+            maps.normal.push(t * 0.5 + Vector3::one() * 0.5);
+        }
+    }
+    maps
+}
+
+impl BakedMaps {
+    /// Writes `<prefix>_albedo.png`, `<prefix>_height.png` and `<prefix>_normal.png`.
+    /// The height map is normalized to its own range, returned as (min, max) in object units.
+    pub fn save(&self, prefix: &str) -> Result<(f32, f32), String> {
+        let (lo, hi) = self.relief.iter().fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), &h| (lo.min(h), hi.max(h)));
+        let span = (hi - lo).max(1e-6);
+        let gray = |h: &f32| Vector3::one() * ((h - lo) / span);
+
+        write_png(&format!("{}_albedo.png", prefix), self.width, self.height, self.albedo.iter().copied())?;
+        write_png(&format!("{}_height.png", prefix), self.width, self.height, self.relief.iter().map(gray))?;
+        write_png(&format!("{}_normal.png", prefix), self.width, self.height, self.normal.iter().copied())?;
+        Ok((lo, hi))
+    }
+}
+
+pub(crate) fn write_png(path: &str, width: u32, height: u32, pixels: impl Iterator<Item = Vector3>) -> Result<(), String> {
+    let mut image = Image::gen_image_color(width as i32, height as i32, Color::BLACK);
+    for (i, c) in pixels.enumerate() {
+        let (x, y) = (i as u32 % width, i as u32 / width);
+        image.draw_pixel(x as i32, y as i32, vec3_to_color(c));
+    }
+    // export_image doesn't report failure: clear any earlier file so its presence means this write worked
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(format!("could not replace {}: {}", path, e)),
+        _ => {}
+    }
+    image.export_image(path);
+    if std::path::Path::new(path).is_file() {
+        Ok(())
+    } else {
+        Err(format!("could not write {}", path))
+    }
+}
@@ -0,0 +1,90 @@
+// src/blackbody.rs
+use raylib::prelude::*;
+use std::sync::OnceLock;
+
+/// Supported effective temperature range (K); inputs are clamped to it
+pub const MIN_KELVIN: f32 = 1000.0;
+pub const MAX_KELVIN: f32 = 40000.0;
+
+const TABLE_SIZE: usize = 256;
+
+/// Display color (sRGB-encoded, brightest channel = 1) of a blackbody at `kelvin`.
+/// Interpolates a table built once from `blackbody_rgb_exact`, spaced evenly in log(T).
+pub fn blackbody_rgb(kelvin: f32) -> Vector3 {
+    static TABLE: OnceLock<Vec<Vector3>> = OnceLock::new();
+    let table = TABLE.get_or_init(|| {
+        (0..TABLE_SIZE)
+            .map(|i| blackbody_rgb_exact(kelvin_at(i as f32 / (TABLE_SIZE - 1) as f32)))
+            .collect()
+    });
+
+    let k = kelvin.clamp(MIN_KELVIN, MAX_KELVIN);
+    let f = (k / MIN_KELVIN).ln() / (MAX_KELVIN / MIN_KELVIN).ln() * (TABLE_SIZE - 1) as f32;
+    let i = (f.floor() as usize).min(TABLE_SIZE - 2);
+    let t = f - i as f32;
+    table[i] * (1.0 - t) + table[i + 1] * t
+}
+
+/// Linear-light color of a blackbody at `kelvin` (brightest channel = 1), for multiplying
+/// into lighting; `blackbody_rgb` is display-encoded and only right as a final color.
+pub fn blackbody_linear(kelvin: f32) -> Vector3 {
+    let c = blackbody_rgb(kelvin);
+    Vector3::new(srgb_decode(c.x), srgb_decode(c.y), srgb_decode(c.z))
+}
+
+fn kelvin_at(t: f32) -> f32 {
+    MIN_KELVIN * (MAX_KELVIN / MIN_KELVIN).powf(t)
+}
+
+/// Integrates Planck's law against the CIE 1931 observer, converts XYZ to sRGB
+/// (D65) and normalizes so the brightest channel is 1.
+pub fn blackbody_rgb_exact(kelvin: f32) -> Vector3 {
+    let t = kelvin.clamp(MIN_KELVIN, MAX_KELVIN) as f64;
+    let (mut x, mut y, mut z) = (0.0f64, 0.0f64, 0.0f64);
+    let mut nm = 380.0f64;
+    while nm <= 780.0 {
+        let b = planck(nm * 1e-9, t);
+        let (xb, yb, zb) = cie_xyz(nm);
+        x += b * xb;
+        y += b * yb;
+        z += b * zb;
+        nm += 5.0;
+    }
+
+    // XYZ -> linear sRGB; out-of-gamut (negative) components are clipped
+    let r = (3.2406 * x - 1.5372 * y - 0.4986 * z).max(0.0);
+    let g = (-0.9689 * x + 1.8758 * y + 0.0415 * z).max(0.0);
+    let bl = (0.0557 * x - 0.2040 * y + 1.0570 * z).max(0.0);
+    let m = r.max(g).max(bl).max(1e-30);
+
+    Vector3::new(srgb_encode((r / m) as f32), srgb_encode((g / m) as f32), srgb_encode((bl / m) as f32))
+}
+
+/// Spectral radiance of a blackbody (arbitrary units; only ratios matter here)
+fn planck(lambda_m: f64, t: f64) -> f64 {
+    const H: f64 = 6.626_070_15e-34;
+    const C: f64 = 2.997_924_58e8;
+    const K: f64 = 1.380_649e-23;
+    let a = 2.0 * H * C * C / lambda_m.powi(5);
+    a / ((H * C / (lambda_m * K * t)).exp() - 1.0)
+}
+
+/// CIE 1931 2° color matching functions, multi-lobe Gaussian fit (Wyman, Sloan & Shirley 2013)
+fn cie_xyz(nm: f64) -> (f64, f64, f64) {
+    let g = |mu: f64, s1: f64, s2: f64| {
+        let s = if nm < mu { s1 } else { s2 };
+        (-0.5 * ((nm - mu) / s).powi(2)).exp()
+    };
+    let x = 1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2);
+    let y = 0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1);
+    let z = 1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8);
+    (x, y, z)
+}
+
+fn srgb_encode(c: f32) -> f32 {
+    if c <= 0.003_130_8 { 12.92 * c } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 }
+}
+
+fn srgb_decode(c: f32) -> f32 {
+    if c <= 0.040_45 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
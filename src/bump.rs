@@ -0,0 +1,81 @@
+// src/bump.rs
+use raylib::prelude::*;
+use std::sync::Arc;
+
+use crate::procedural::NoiseBasis;
+use crate::texture::Texture;
+
+/// Fragment-stage relief: the shading normal is tilted by the gradient of a height field,
+/// so detail finer than the mesh tessellation still catches the light.
+/// The height is the sum of the enabled sources (see `shaders::surface_height`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BumpParams {
+    pub strength: f32,   // 1 = slopes of the height field as-is
+    pub epsilon: f32,    // finite-difference step (object-space units)
+
+    // FBM relief; match the vertex shader's displacement and add octaves for finer detail
+    pub amp: f32, pub freq: f32, pub octaves: u32, pub lacunarity: f32, pub gain: f32,
+    pub basis: NoiseBasis,
+
+    pub terrain_amp: f32, // relief from `Material::terrain` above sea level (oceans stay flat)
+    pub craters: bool,    // include `Material::craters`
+}
+
+impl BumpParams {
+    /// FBM-only relief
+    pub fn noise(amp: f32, freq: f32, octaves: u32, basis: NoiseBasis) -> Self {
+        Self {
+            strength: 1.0,
+            epsilon: 0.002,
+            amp, freq, octaves, lacunarity: 2.0, gain: 0.5,
+            basis,
+            terrain_amp: 0.0,
+            craters: false,
+        }
+    }
+}
+
+/// Tangent-space normal map (OpenGL convention: +Y green = +bitangent) for meshes with UVs
+#[derive(Clone)]
+pub struct NormalMap {
+    pub texture: Arc<Texture>,
+    pub strength: f32,   // 0 = flat, 1 = as authored
+}
+
+impl NormalMap {
+    pub fn load(path: &str, strength: f32) -> Result<Self, String> {
+        Ok(NormalMap { texture: Arc::new(Texture::load(path)?), strength })
+    }
+}
+
+/// Tilts unit normal `n` (object space) by the gradient of `height` around point `p`
+pub fn bumped_normal(p: Vector3, n: Vector3, strength: f32, epsilon: f32, height: impl Fn(Vector3) -> f32) -> Vector3 {
+    let (t1, t2) = tangent_frame(n);
+    let h0 = height(p);
+    let d1 = (height(p + t1 * epsilon) - h0) / epsilon;
+    let d2 = (height(p + t2 * epsilon) - h0) / epsilon;
+    (n - (t1 * d1 + t2 * d2) * strength).normalized()
+}
+
+/// Applies a tangent-space normal map texel (0..1 RGB). `n`, `tangent` and `bitangent` are world-space
+/// (interpolated, not necessarily orthonormal); the bitangent only decides handedness.
+pub fn normal_mapped(n: Vector3, tangent: Vector3, bitangent: Vector3, texel: Vector3, strength: f32) -> Vector3 {
+    let n = n.normalized();
+    let t = tangent - n * n.dot(tangent);
+    if t.length() < 1e-6 {
+        return n;
+    }
+    let t = t.normalized();
+    let mut b = n.cross(t);
+    if b.dot(bitangent) < 0.0 {
+        b = -b; // mirrored UVs
+    }
+    let s = texel * 2.0 - Vector3::one();
+    (t * (s.x * strength) + b * (s.y * strength) + n * s.z).normalized()
+}
+
+fn tangent_frame(n: Vector3) -> (Vector3, Vector3) {
+    let helper = if n.y.abs() < 0.9 { Vector3::new(0.0, 1.0, 0.0) } else { Vector3::new(1.0, 0.0, 0.0) };
+    let t1 = helper.cross(n).normalized();
+    (t1, n.cross(t1))
+}
@@ -65,6 +65,20 @@ impl Camera {
     pub fn get_view_matrix(&self) -> Matrix {
         create_view_matrix(self.eye, self.target, self.up)
     }
+    /// World-space view direction through pixel (px, py) for a perspective of vertical `fov_y`
+    pub fn ray_direction(&self, px: f32, py: f32, width: f32, height: f32, fov_y: f32) -> Vector3 {
+        let forward = (self.target - self.eye).normalized();
+        let right = forward.cross(self.up).normalized();
+        let up = right.cross(forward);
+
+        // Pixel center to NDC (viewport flips Y)
+        let tan_half = (fov_y / 2.0).tan();
+        let ndc_x = 2.0 * (px + 0.5) / width - 1.0;
+        let ndc_y = 1.0 - 2.0 * (py + 0.5) / height;
+
+        (forward + right * (ndc_x * tan_half * width / height) + up * (ndc_y * tan_half)).normalized()
+    }
+
     pub fn set_target(&mut self, new_target: Vector3) {
         self.target = new_target;
         self.update_eye_position();
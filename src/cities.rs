@@ -0,0 +1,62 @@
+// src/cities.rs
+use raylib::prelude::*;
+
+use crate::blackbody::blackbody_rgb;
+use crate::procedural::{noise3, smoothstep, worley_noise3, NoiseBasis};
+
+/// Emissive city lights for inhabited planets: clustered settlements on land,
+/// visible only on the night side and fading in across the terminator.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CityLightParams {
+    // Where people live: low-frequency "civilization" regions
+    pub region_freq: f32,
+    pub region_threshold: f32,  // 0..1; higher leaves more land dark
+    // Individual cities and towns: Worley cells, lit near each cell center
+    pub city_freq: f32,
+    pub city_size: f32,         // radius of a city in Worley distance units
+    pub sprawl_freq: f32,       // fine noise breaking up each city's footprint
+
+    pub temperature: f32,       // lamp color (K): sodium ~2000, LED ~4000
+    pub intensity: f32,
+    pub terminator_width: f32,  // in cos(sun angle): lights ramp in over [-w, w]
+}
+
+impl CityLightParams {
+    pub fn earth() -> Self {
+        Self {
+            region_freq: 2.2,
+            region_threshold: 0.5,
+            city_freq: 26.0,
+            city_size: 0.35,
+            sprawl_freq: 90.0,
+            temperature: 2300.0,
+            intensity: 1.3,
+            terminator_width: 0.12,
+        }
+    }
+}
+
+/// Emitted light at object-space point `p`. `land` is 1 on habitable land (0 on water/ice),
+/// `cos_sun` the cosine between the surface normal and the direction to the star.
+pub fn city_lights(p: Vector3, land: f32, cos_sun: f32, c: &CityLightParams, seed: u32) -> Vector3 {
+    let night = 1.0 - smoothstep(-c.terminator_width, c.terminator_width, cos_sun);
+    if night <= 0.0 || land <= 0.0 {
+        return Vector3::zero();
+    }
+    let dir = p.normalized();
+
+    // Populated regions
+    let region = noise3(NoiseBasis::Perlin, dir * c.region_freq, 3, 2.0, 0.5, seed ^ 0xC17E_0001) * 0.5 + 0.5;
+    let populated = smoothstep(c.region_threshold, c.region_threshold + 0.15, region);
+    if populated <= 0.0 {
+        return Vector3::zero();
+    }
+
+    // Cities around Worley feature points, bright cores and dimmer suburbs
+    let (f1, _) = worley_noise3(dir * c.city_freq, seed ^ 0xC17E_0002);
+    let city = 1.0 - smoothstep(0.0, c.city_size, f1);
+    let sprawl = noise3(NoiseBasis::Value, dir * c.sprawl_freq, 2, 2.0, 0.5, seed ^ 0xC17E_0003) * 0.5 + 0.5;
+    let lit = city * city * (0.4 + 0.6 * sprawl);
+
+    blackbody_rgb(c.temperature) * (c.intensity * lit * populated * land * night)
+}
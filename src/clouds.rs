@@ -0,0 +1,93 @@
+// src/clouds.rs
+use raylib::prelude::*;
+
+use crate::procedural::{curl_noise3, noise3, smoothstep, NoiseBasis};
+
+/// Cloud layer drawn on a slightly larger, alpha-blended shell around a planet.
+/// The planet's own material takes the same params to shade the cloud shadows on the ground,
+/// so both must share `seed`; the shell must also share the planet's rotation and spin.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CloudParams {
+    pub seed: u32,
+    pub shell_scale: f32,     // shell radius / planet radius
+
+    // Coverage: FBM thresholded at `coverage`, advected by curl noise
+    pub freq: f32,
+    pub octaves: u32,
+    pub coverage: f32,        // 0 clear .. 1 overcast
+    pub softness: f32,        // width of the cloud edge
+    pub curl_freq: f32,
+    pub curl_strength: f32,
+    pub evolve_speed: f32,    // how fast the pattern changes shape
+    pub drift: f32,           // rotation (rad/s) relative to the planet's spin
+
+    pub color: Vector3,
+    pub opacity: f32,         // alpha of fully covered sky
+
+    // Shadows cast on the surface below
+    pub shadow_strength: f32,
+    pub shadow_softness: f32, // extra edge width of the shadow
+}
+
+impl CloudParams {
+    pub fn earth() -> Self {
+        Self {
+            seed: 0,
+            shell_scale: 1.04,
+            freq: 2.6,
+            octaves: 5,
+            coverage: 0.55,
+            softness: 0.10,
+            curl_freq: 1.5,
+            curl_strength: 0.25,
+            evolve_speed: 0.04,
+            drift: 0.12,
+            color: Vector3::new(0.97, 0.97, 1.0),
+            opacity: 0.9,
+            shadow_strength: 0.45,
+            shadow_softness: 0.12,
+        }
+    }
+}
+
+/// Raw coverage field (~0..1) at an object-space direction, before thresholding
+fn coverage_field(dir: Vector3, c: &CloudParams, time: f32) -> f32 {
+    // Layer drift relative to the ground
+    let (s, co) = (c.drift * time).sin_cos();
+    let d = Vector3::new(co * dir.x - s * dir.z, dir.y, s * dir.x + co * dir.z);
+
+    // Slow evolution: slide through the 3D field, and swirl along a curl flow
+    let t = Vector3::new(0.0, time * c.evolve_speed, time * c.evolve_speed * 0.6);
+    let q = d * c.freq + t;
+    let flow = curl_noise3(d * c.curl_freq + t * 0.5, c.seed ^ 0xC10D_0001);
+    let f = noise3(NoiseBasis::Perlin, q + flow * c.curl_strength, c.octaves, 2.0, 0.5, c.seed) * 0.5 + 0.5;
+
+    // Thinner clouds in the subtropical dry belts, more along the equator and mid-latitudes
+    let lat = d.y.abs();
+    let belts = 1.0 - 0.25 * (1.0 - ((lat - 0.45).abs() / 0.15).min(1.0));
+    f * belts
+}
+
+/// Cloud opacity (0..1) on the shell at object-space point `p`
+pub fn cloud_alpha(p: Vector3, c: &CloudParams, time: f32) -> f32 {
+    let f = coverage_field(p.normalized(), c, time);
+    let edge = 1.0 - c.coverage;
+    smoothstep(edge - c.softness, edge + c.softness, f) * c.opacity
+}
+
+/// Light left after passing through the cloud layer, for a ground point `p` on the planet
+/// and the object-space direction `light_dir` toward the star
+pub fn cloud_shadow(p: Vector3, light_dir: Vector3, c: &CloudParams, time: f32) -> f32 {
+    let dir = p.normalized();
+    let l = light_dir.normalized();
+    let cos_l = dir.dot(l);
+    if cos_l <= 0.0 {
+        return 1.0; // night side: nothing to shadow
+    }
+    // Where the ray toward the star crosses the shell (small-height approximation)
+    let hit = (dir + l * ((c.shell_scale - 1.0) / cos_l.max(0.15))).normalized();
+    let f = coverage_field(hit, c, time);
+    let edge = 1.0 - c.coverage;
+    let soft = c.softness + c.shadow_softness;
+    1.0 - c.shadow_strength * c.opacity * smoothstep(edge - soft, edge + soft, f)
+}
@@ -0,0 +1,147 @@
+// src/craters.rs
+use raylib::prelude::*;
+
+use crate::procedural::{hash_to_unit, hash_u32, lattice_hash, smoothstep};
+
+/// Parameters of a seeded impact-crater field on the unit sphere.
+/// Craters live on a hashed lattice (one candidate per cell and size class), so the field is
+/// stateless: the vertex stage and the fragment stage evaluate exactly the same craters.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CraterParams {
+    pub size_classes: u32,     // each class halves the radius (and has ~4x more cells)
+    pub max_radius: f32,       // radius of the largest class (unit-sphere units)
+    pub density: f32,          // chance that a cell of the largest class holds a crater
+    pub density_gain: f32,     // density multiplier per smaller class (size distribution)
+    pub depth: f32,            // bowl depth relative to the crater radius
+    pub rim_height: f32,       // rim height relative to the bowl depth
+    pub floor: f32,            // flat floor level in profile units (-1 bowl bottom .. 0 rim)
+    pub peak_radius: f32,      // craters at least this large get a central peak
+    pub ejecta: f32,           // height of the ejecta blanket just outside the rim
+    pub ray_fraction: f32,     // share of craters young enough to show bright rays
+}
+
+impl CraterParams {
+    pub fn lunar() -> Self {
+        Self {
+            size_classes: 4,
+            max_radius: 0.22,
+            density: 0.55,
+            density_gain: 1.15,
+            depth: 0.35,
+            rim_height: 0.30,
+            floor: -0.65,
+            peak_radius: 0.12,
+            ejecta: 0.08,
+            ray_fraction: 0.25,
+        }
+    }
+}
+
+/// What the crater field looks like at one point of the surface.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CraterSample {
+    pub height: f32, // displacement in unit-sphere units (negative in bowls)
+    pub floor: f32,  // 0..1, how far inside a crater floor we are
+    pub rays: f32,   // 0..1, bright ejecta ray coverage
+}
+
+// Cells are this many radii wide so that a crater's ejecta never leaves the 3x3x3 neighborhood
+const CELL_IN_RADII: f32 = 5.0;
+const REACH_IN_RADII: f32 = 2.5;
+const RIM_WIDTH: f32 = 0.7;
+
+/// Samples the crater field at `p` (any point; it is projected onto the unit sphere).
+pub fn crater_field(p: Vector3, params: &CraterParams, seed: u32) -> CraterSample {
+    let dir = p.normalized();
+    let mut out = CraterSample::default();
+
+    let mut radius = params.max_radius;
+    let mut density = params.density;
+    for class in 0..params.size_classes {
+        let cell = radius * CELL_IN_RADII;
+        let q = dir / cell;
+        let (cx, cy, cz) = (q.x.floor() as i32, q.y.floor() as i32, q.z.floor() as i32);
+
+        for dz in -1..=1 {
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    let (i, j, k) = (cx + dx, cy + dy, cz + dz);
+                    let mut h = lattice_hash(i, j, k, seed ^ class.wrapping_mul(0x9E37_79B9));
+                    if hash_to_unit(h) > density {
+                        continue;
+                    }
+                    let mut next = || { h = hash_u32(h); hash_to_unit(h) };
+                    let local = Vector3::new(next(), next(), next());
+                    let center = (Vector3::new(i as f32, j as f32, k as f32) + local) * cell;
+                    // Only cells straddling the sphere surface project cleanly onto it
+                    if (center.length() - 1.0).abs() > cell * 0.5 {
+                        continue;
+                    }
+                    let center = center.normalized();
+                    // Radius within the class: [0.5, 1] of the nominal size
+                    let r = radius * (0.5 + 0.5 * next());
+                    let d = (dir - center).length();
+                    let x = d / r;
+                    if x > REACH_IN_RADII {
+                        continue;
+                    }
+
+                    out.height += crater_profile(x, r, params);
+                    out.floor = out.floor.max(1.0 - smoothstep(0.6, 1.0, x));
+
+                    if next() < params.ray_fraction && x > 0.9 {
+                        let n_rays = 5.0 + (next() * 9.0).floor();
+                        let phase = next() * std::f32::consts::TAU;
+                        let ang = tangent_angle(dir, center);
+                        let spoke = ((ang * n_rays * 0.5 + phase).sin().abs()).powf(12.0);
+                        let fade = 1.0 - smoothstep(1.0, REACH_IN_RADII, x);
+                        out.rays = out.rays.max(spoke * fade);
+                    }
+                }
+            }
+        }
+
+        radius *= 0.5;
+        density = (density * params.density_gain).min(1.0);
+    }
+    out
+}
+
+/// Radial crater shape at normalized distance `x` (= distance / radius), in unit-sphere units.
+/// Parabolic bowl with a flat floor, raised rim, optional central peak and an ejecta blanket.
+fn crater_profile(x: f32, radius: f32, params: &CraterParams) -> f32 {
+    let cavity = x * x - 1.0;
+    let rim_x = (x - 1.0 - RIM_WIDTH).min(0.0);
+    let rim = params.rim_height / (RIM_WIDTH * RIM_WIDTH) * rim_x * rim_x;
+    let mut shape = smin(cavity, rim, 0.25);
+    shape = smax(shape, params.floor, 0.25);
+
+    if radius >= params.peak_radius {
+        shape += 0.45 * (-(x / 0.22) * (x / 0.22)).exp();
+    }
+    if x > 1.0 {
+        let fade = 1.0 - smoothstep(1.0 + RIM_WIDTH, REACH_IN_RADII, x);
+        shape += params.ejecta * fade / (x * x * x);
+    }
+    shape * params.depth * radius
+}
+
+/// Angle of `dir` around `center` in a tangent frame at the crater
+fn tangent_angle(dir: Vector3, center: Vector3) -> f32 {
+    let helper = if center.y.abs() < 0.9 { Vector3::new(0.0, 1.0, 0.0) } else { Vector3::new(1.0, 0.0, 0.0) };
+    let t1 = center.cross(helper).normalized();
+    let t2 = center.cross(t1);
+    let d = dir - center;
+    d.dot(t2).atan2(d.dot(t1))
+}
+
+#[inline]
+fn smin(a: f32, b: f32, k: f32) -> f32 {
+    let h = (0.5 + 0.5 * (b - a) / k).clamp(0.0, 1.0);
+    b + (a - b) * h - k * h * (1.0 - h)
+}
+
+#[inline]
+fn smax(a: f32, b: f32, k: f32) -> f32 {
+    -smin(-a, -b, k)
+}
@@ -0,0 +1,799 @@
+// src/expr.rs
+use raylib::prelude::*;
+use std::fmt;
+
+use crate::procedural::{clamp3, each, mix3, noise3, MAX_OCTAVES, smoothstep3, splat, worley_noise3, xy, zip, NoiseBasis};
+use crate::ramp::{ColorRamp, RampMode};
+use crate::shaders::{palette_cosine, pattern_checker, pattern_rings, pattern_stripes, Material};
+
+/// Per-entity fragment shader written as small assignments in a text file, compiled to a flat
+/// stack program and run as a `LayerKind::Expr` layer:
+///
+/// ```text
+/// # gas bands warped by noise
+/// w = fbm(p * 3.0 + vec3(0, 0, t * 0.2))
+/// color = palette(p.y * 2.0 + w * 0.5, vec3(0.5), vec3(0.5), vec3(1.0), vec3(0.0, 0.33, 0.67))
+/// alpha = 1
+/// ```
+///
+/// Statements end at a newline or `;`; `#` and `//` start comments. The program must assign
+/// `color` and may assign `alpha` (layer coverage). Every value is a vec3; scalars are splatted,
+/// and `.x` `.y` `.z` pick a component.
+///
+/// Inputs: `p` (object-space position), `n` (world normal), `world`, `uv` (screen, [-.5, .5]),
+/// `frag` (pixel), `t`/`time`, `resolution`, `base` (color below this layer), `pi`, `tau`,
+/// and the material's `pal1_a`..`pal2_d`, `pal_mix_radius`, `crater_floor_dark`, `crater_ray_bright`.
+/// `material_palette(t)` / `material_palette2(t)` sample the material's palettes (its ramps if set);
+/// `ramp(t, pos0, c0, pos1, c1, ...)` is a `ColorRamp` through constant stops, blended in OKLab;
+/// `ramp_linear` and `ramp_smooth` take the same stops with RGB and eased blends.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExprProgram {
+    code: Vec<Instr>,
+    slots: usize,        // local variables, including `color` and `alpha`
+    color_slot: usize,
+    alpha_slot: Option<usize>,
+    ramps: Vec<ColorRamp>, // built from constant stops at compile time, indexed by Instr::Ramp
+}
+
+/// Syntax or compile error at a 1-based line and column
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExprError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ExprError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+/// What a program can read at a fragment
+pub struct ExprInputs<'a> {
+    pub position: Vector3,
+    pub normal: Vector3,
+    pub world: Vector3,
+    pub uv: Vector2,
+    pub frag: Vector2,
+    pub time: f32,
+    pub resolution: Vector2,
+    pub base: Vector3,
+    pub material: &'a Material,
+}
+
+// -------------------- Lexer --------------------
+#[derive(Clone, Debug, PartialEq)]
+enum Tok {
+    Num(f32),
+    Ident(String),
+    Op(char), // + - * / = ( ) , . ;
+    Newline,
+    Eof,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Pos {
+    line: usize,
+    column: usize,
+}
+
+fn error(pos: Pos, message: impl Into<String>) -> ExprError {
+    ExprError { line: pos.line, column: pos.column, message: message.into() }
+}
+
+fn lex(src: &str) -> Result<Vec<(Tok, Pos)>, ExprError> {
+    let mut out = Vec::new();
+    let chars: Vec<char> = src.chars().collect();
+    let (mut i, mut line, mut col) = (0, 1, 1);
+    let mut depth = 0usize; // newlines inside parentheses do not end a statement
+    while i < chars.len() {
+        let c = chars[i];
+        let pos = Pos { line, column: col };
+        if c == '\n' {
+            if depth == 0 {
+                out.push((Tok::Newline, pos));
+            }
+            i += 1;
+            line += 1;
+            col = 1;
+            continue;
+        }
+        if c.is_whitespace() {
+            i += 1;
+            col += 1;
+            continue;
+        }
+        if c == '#' || (c == '/' && chars.get(i + 1) == Some(&'/')) {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            continue;
+        }
+        let start = i;
+        if c.is_ascii_digit() || (c == '.' && chars.get(i + 1).is_some_and(|d| d.is_ascii_digit())) {
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                i += 1;
+                if i < chars.len() && (chars[i] == '+' || chars[i] == '-') {
+                    i += 1;
+                }
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+            }
+            let text: String = chars[start..i].iter().collect();
+            let value = text.parse().map_err(|_| error(pos, format!("invalid number `{}`", text)))?;
+            out.push((Tok::Num(value), pos));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            out.push((Tok::Ident(chars[start..i].iter().collect()), pos));
+        } else if "+-*/=(),.;".contains(c) {
+            match c {
+                '(' => depth += 1,
+                ')' => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            out.push((Tok::Op(c), pos));
+            i += 1;
+        } else {
+            return Err(error(pos, format!("unexpected character `{}`", c)));
+        }
+        col += i - start;
+    }
+    out.push((Tok::Eof, Pos { line, column: col }));
+    Ok(out)
+}
+
+// -------------------- Parser --------------------
+#[derive(Clone, Debug)]
+enum Expr {
+    Num(f32),
+    Var(String, Pos),
+    Call(String, Vec<Expr>, Pos),
+    Neg(Box<Expr>),
+    Bin(char, Box<Expr>, Box<Expr>),
+    Member(Box<Expr>, usize, Pos),
+}
+
+struct Parser {
+    toks: Vec<(Tok, Pos)>,
+    at: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Tok {
+        &self.toks[self.at].0
+    }
+    fn pos(&self) -> Pos {
+        self.toks[self.at].1
+    }
+    fn next(&mut self) -> (Tok, Pos) {
+        let t = self.toks[self.at].clone();
+        if self.at + 1 < self.toks.len() {
+            self.at += 1;
+        }
+        t
+    }
+    fn expect(&mut self, c: char) -> Result<(), ExprError> {
+        match self.next() {
+            (Tok::Op(o), _) if o == c => Ok(()),
+            (t, pos) => Err(error(pos, format!("expected `{}`, found {}", c, describe(&t)))),
+        }
+    }
+
+    fn statements(&mut self) -> Result<Vec<(String, Pos, Expr)>, ExprError> {
+        let mut out = Vec::new();
+        loop {
+            while matches!(self.peek(), Tok::Newline | Tok::Op(';')) {
+                self.next();
+            }
+            let (tok, pos) = self.next();
+            let name = match tok {
+                Tok::Eof => return Ok(out),
+                Tok::Ident(name) => name,
+                t => return Err(error(pos, format!("expected a variable name, found {}", describe(&t)))),
+            };
+            self.expect('=')?;
+            let value = self.expr()?;
+            match self.peek() {
+                Tok::Newline | Tok::Op(';') | Tok::Eof => {}
+                t => return Err(error(self.pos(), format!("expected end of statement, found {}", describe(t)))),
+            }
+            out.push((name, pos, value));
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, ExprError> {
+        let mut lhs = self.term()?;
+        while let Tok::Op(c @ ('+' | '-')) = *self.peek() {
+            self.next();
+            lhs = Expr::Bin(c, Box::new(lhs), Box::new(self.term()?));
+        }
+        Ok(lhs)
+    }
+
+    fn term(&mut self) -> Result<Expr, ExprError> {
+        let mut lhs = self.unary()?;
+        while let Tok::Op(c @ ('*' | '/')) = *self.peek() {
+            self.next();
+            lhs = Expr::Bin(c, Box::new(lhs), Box::new(self.unary()?));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, ExprError> {
+        if *self.peek() == Tok::Op('-') {
+            self.next();
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        let mut e = self.primary()?;
+        while *self.peek() == Tok::Op('.') {
+            self.next();
+            let (tok, pos) = self.next();
+            let component = match &tok {
+                Tok::Ident(s) if s == "x" || s == "r" => 0,
+                Tok::Ident(s) if s == "y" || s == "g" => 1,
+                Tok::Ident(s) if s == "z" || s == "b" => 2,
+                t => return Err(error(pos, format!("expected `x`, `y` or `z` after `.`, found {}", describe(t)))),
+            };
+            e = Expr::Member(Box::new(e), component, pos);
+        }
+        Ok(e)
+    }
+
+    fn primary(&mut self) -> Result<Expr, ExprError> {
+        let (tok, pos) = self.next();
+        match tok {
+            Tok::Num(v) => Ok(Expr::Num(v)),
+            Tok::Ident(name) => {
+                if *self.peek() != Tok::Op('(') {
+                    return Ok(Expr::Var(name, pos));
+                }
+                self.next();
+                let mut args = Vec::new();
+                if *self.peek() != Tok::Op(')') {
+                    loop {
+                        args.push(self.expr()?);
+                        if *self.peek() == Tok::Op(',') {
+                            self.next();
+                        } else {
+                            break;
+                        }
+                    }
+                }
+                self.expect(')')?;
+                Ok(Expr::Call(name, args, pos))
+            }
+            Tok::Op('(') => {
+                let e = self.expr()?;
+                self.expect(')')?;
+                Ok(e)
+            }
+            t => Err(error(pos, format!("expected a value, found {}", describe(&t)))),
+        }
+    }
+}
+
+fn describe(t: &Tok) -> String {
+    match t {
+        Tok::Num(v) => format!("number `{}`", v),
+        Tok::Ident(s) => format!("`{}`", s),
+        Tok::Op(c) => format!("`{}`", c),
+        Tok::Newline => "end of line".into(),
+        Tok::Eof => "end of file".into(),
+    }
+}
+
+// -------------------- Compiler --------------------
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Input {
+    Position, Normal, World, Uv, Frag, Time, Resolution, Base,
+    Pal1A, Pal1B, Pal1C, Pal1D, Pal2A, Pal2B, Pal2C, Pal2D,
+    PalMixRadius, CraterFloorDark, CraterRayBright,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Func {
+    Sin, Cos, Abs, Floor, Fract, Sqrt, Normalize, Length, Exp,
+    Pow, Min, Max, Dot, Step, Cross,
+    Mix, Clamp, Smoothstep,
+    Vec3,
+    Noise, Fbm, Ridged, Billow, Simplex, Worley, Warp,
+    Palette, MaterialPalette, MaterialPalette2,
+    Rings, Stripes, Checker,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Instr {
+    Const(Vector3),
+    Input(Input),
+    Load(usize),
+    Store(usize),
+    Neg,
+    Bin(char),
+    Member(usize),
+    Call(Func, usize), // function and argument count
+    Ramp(usize),       // samples `ExprProgram::ramps[i]` at the top value's x
+}
+
+fn input_named(name: &str) -> Option<Input> {
+    Some(match name {
+        "p" => Input::Position,
+        "n" => Input::Normal,
+        "world" => Input::World,
+        "uv" => Input::Uv,
+        "frag" => Input::Frag,
+        "t" | "time" => Input::Time,
+        "resolution" => Input::Resolution,
+        "base" => Input::Base,
+        "pal1_a" => Input::Pal1A, "pal1_b" => Input::Pal1B, "pal1_c" => Input::Pal1C, "pal1_d" => Input::Pal1D,
+        "pal2_a" => Input::Pal2A, "pal2_b" => Input::Pal2B, "pal2_c" => Input::Pal2C, "pal2_d" => Input::Pal2D,
+        "pal_mix_radius" => Input::PalMixRadius,
+        "crater_floor_dark" => Input::CraterFloorDark,
+        "crater_ray_bright" => Input::CraterRayBright,
+        _ => return None,
+    })
+}
+
+/// Function by name with its accepted argument counts
+fn func_named(name: &str) -> Option<(Func, &'static [usize])> {
+    Some(match name {
+        "sin" => (Func::Sin, &[1]),
+        "cos" => (Func::Cos, &[1]),
+        "abs" => (Func::Abs, &[1]),
+        "floor" => (Func::Floor, &[1]),
+        "fract" => (Func::Fract, &[1]),
+        "sqrt" => (Func::Sqrt, &[1]),
+        "exp" => (Func::Exp, &[1]),
+        "normalize" => (Func::Normalize, &[1]),
+        "length" => (Func::Length, &[1]),
+        "pow" => (Func::Pow, &[2]),
+        "min" => (Func::Min, &[2]),
+        "max" => (Func::Max, &[2]),
+        "dot" => (Func::Dot, &[2]),
+        "step" => (Func::Step, &[2]),
+        "cross" => (Func::Cross, &[2]),
+        "mix" => (Func::Mix, &[3]),
+        "clamp" => (Func::Clamp, &[3]),
+        "smoothstep" => (Func::Smoothstep, &[3]),
+        "vec3" => (Func::Vec3, &[1, 3]),
+        "noise" => (Func::Noise, &[1]),
+        "fbm" => (Func::Fbm, &[1, 2]),
+        "ridged" => (Func::Ridged, &[1, 2]),
+        "billow" => (Func::Billow, &[1, 2]),
+        "simplex" => (Func::Simplex, &[1, 2]),
+        "worley" => (Func::Worley, &[1]),
+        "warp" => (Func::Warp, &[1, 2]),
+        "palette" => (Func::Palette, &[5]),
+        "material_palette" => (Func::MaterialPalette, &[1]),
+        "material_palette2" => (Func::MaterialPalette2, &[1]),
+        "rings" => (Func::Rings, &[3]),
+        "stripes" => (Func::Stripes, &[4]),
+        "checker" => (Func::Checker, &[2]),
+        _ => return None,
+    })
+}
+
+/// Ramp functions: `t` then (position, color) pairs, blended in this mode
+fn ramp_named(name: &str) -> Option<RampMode> {
+    match name {
+        "ramp" => Some(RampMode::Oklab),
+        "ramp_linear" => Some(RampMode::Linear),
+        "ramp_smooth" => Some(RampMode::Smoothstep),
+        _ => None,
+    }
+}
+
+/// Functions that only depend on their arguments, safe to evaluate at compile time
+fn is_pure_math(f: Func) -> bool {
+    !matches!(f, Func::Noise | Func::Fbm | Func::Ridged | Func::Billow | Func::Simplex | Func::Worley
+        | Func::Warp | Func::MaterialPalette | Func::MaterialPalette2 | Func::Rings | Func::Stripes | Func::Checker)
+}
+
+/// Variables plus evaluation stack a program may use; `eval` keeps them in a fixed array
+const MAX_REGISTERS: usize = 64;
+
+struct Compiler {
+    code: Vec<Instr>,
+    ramps: Vec<ColorRamp>,
+    vars: Vec<String>,
+    depth: usize,
+    max_depth: usize,
+}
+
+impl Compiler {
+    fn emit(&mut self, instr: Instr, stack_delta: isize) {
+        self.code.push(instr);
+        self.depth = (self.depth as isize + stack_delta) as usize;
+        self.max_depth = self.max_depth.max(self.depth);
+    }
+
+    fn slot(&mut self, name: &str) -> usize {
+        match self.vars.iter().position(|v| v == name) {
+            Some(i) => i,
+            None => {
+                self.vars.push(name.to_string());
+                self.vars.len() - 1
+            }
+        }
+    }
+
+    /// Folds constant subtrees, then emits the rest
+    fn expr(&mut self, e: &Expr, assigned: &[bool]) -> Result<(), ExprError> {
+        if let Some(v) = fold(e) {
+            self.emit(Instr::Const(v), 1);
+            return Ok(());
+        }
+        match e {
+            Expr::Num(v) => self.emit(Instr::Const(splat(*v)), 1),
+            Expr::Var(name, pos) => {
+                if let Some(i) = self.vars.iter().position(|v| v == name).filter(|&i| assigned[i]) {
+                    self.emit(Instr::Load(i), 1);
+                } else if let Some(c) = constant_named(name) {
+                    self.emit(Instr::Const(c), 1);
+                } else if let Some(input) = input_named(name) {
+                    self.emit(Instr::Input(input), 1);
+                } else {
+                    return Err(error(*pos, format!("unknown variable `{}`", name)));
+                }
+            }
+            Expr::Neg(a) => {
+                self.expr(a, assigned)?;
+                self.emit(Instr::Neg, 0);
+            }
+            Expr::Bin(op, a, b) => {
+                self.expr(a, assigned)?;
+                self.expr(b, assigned)?;
+                self.emit(Instr::Bin(*op), -1);
+            }
+            Expr::Member(a, c, _) => {
+                self.expr(a, assigned)?;
+                self.emit(Instr::Member(*c), 0);
+            }
+            Expr::Call(name, args, pos) if ramp_named(name).is_some() => {
+                if args.len() < 3 || args.len() % 2 == 0 {
+                    return Err(error(*pos, format!("`{}` takes t and then position, color pairs, got {} argument(s)", name, args.len())));
+                }
+                let stops = args[1..].chunks_exact(2)
+                    .map(|pair| Some((fold(&pair[0])?.x, fold(&pair[1])?)))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| error(*pos, format!("`{}` stop positions and colors must be constant", name)))?;
+                self.ramps.push(ColorRamp::new(ramp_named(name).unwrap_or(RampMode::Oklab), &stops));
+                self.expr(&args[0], assigned)?;
+                self.emit(Instr::Ramp(self.ramps.len() - 1), 0);
+            }
+            Expr::Call(name, args, pos) => {
+                let (f, arities) = func_named(name).ok_or_else(|| error(*pos, format!("unknown function `{}`", name)))?;
+                if !arities.contains(&args.len()) {
+                    let expected: Vec<String> = arities.iter().map(|n| n.to_string()).collect();
+                    return Err(error(*pos, format!("`{}` takes {} argument(s), got {}", name, expected.join(" or "), args.len())));
+                }
+                for a in args {
+                    self.expr(a, assigned)?;
+                }
+                self.emit(Instr::Call(f, args.len()), 1 - args.len() as isize);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn constant_named(name: &str) -> Option<Vector3> {
+    match name {
+        "pi" => Some(splat(std::f32::consts::PI)),
+        "tau" => Some(splat(std::f32::consts::TAU)),
+        _ => None,
+    }
+}
+
+/// Value of `e` if it is made only of literals, constants and pure math
+fn fold(e: &Expr) -> Option<Vector3> {
+    match e {
+        Expr::Num(v) => Some(splat(*v)),
+        Expr::Var(name, _) => constant_named(name),
+        Expr::Neg(a) => fold(a).map(|v| -v),
+        Expr::Bin(op, a, b) => Some(binary(*op, fold(a)?, fold(b)?)),
+        Expr::Member(a, c, _) => fold(a).map(|v| splat(component(v, *c))),
+        Expr::Call(name, args, _) => {
+            let (f, arities) = func_named(name)?;
+            if !is_pure_math(f) || !arities.contains(&args.len()) {
+                return None;
+            }
+            let values: Vec<Vector3> = args.iter().map(fold).collect::<Option<_>>()?;
+            Some(call_pure(f, &values))
+        }
+    }
+}
+
+impl ExprProgram {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let src = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Self::parse(&src).map_err(|e| format!("{}:{}", path, e))
+    }
+
+    pub fn parse(src: &str) -> Result<Self, ExprError> {
+        let mut parser = Parser { toks: lex(src)?, at: 0 };
+        let statements = parser.statements()?;
+
+        let mut c = Compiler { code: Vec::new(), ramps: Vec::new(), vars: vec!["color".into(), "alpha".into()], depth: 0, max_depth: 0 };
+        let mut assigned = vec![false, false];
+        for (name, pos, value) in &statements {
+            if input_named(name).is_some() || constant_named(name).is_some() {
+                return Err(error(*pos, format!("`{}` is a built-in input and cannot be assigned", name)));
+            }
+            c.expr(value, &assigned)?;
+            let slot = c.slot(name);
+            if slot >= assigned.len() {
+                assigned.push(false);
+            }
+            assigned[slot] = true;
+            c.emit(Instr::Store(slot), -1);
+        }
+        let end = parser.toks.last().map_or(Pos { line: 1, column: 1 }, |t| t.1);
+        if !assigned[0] {
+            return Err(error(end, "the program never assigns `color`"));
+        }
+        if c.vars.len() + c.max_depth > MAX_REGISTERS {
+            return Err(error(end, format!("the program needs {} registers (variables plus stack), at most {}",
+                c.vars.len() + c.max_depth, MAX_REGISTERS)));
+        }
+        Ok(ExprProgram {
+            code: c.code,
+            slots: c.vars.len(),
+            color_slot: 0,
+            alpha_slot: assigned[1].then_some(1),
+            ramps: c.ramps,
+        })
+    }
+
+    /// Color and coverage (`alpha`, 1 if never assigned) at one fragment
+    pub fn eval(&self, inputs: &ExprInputs) -> (Vector3, f32) {
+        // Variables first, the evaluation stack above them; `parse` bounds both by MAX_REGISTERS
+        let mut regs = [Vector3::zero(); MAX_REGISTERS];
+        let mut top = self.slots;
+        for instr in &self.code {
+            match *instr {
+                Instr::Const(v) => {
+                    regs[top] = v;
+                    top += 1;
+                }
+                Instr::Input(i) => {
+                    regs[top] = read_input(i, inputs);
+                    top += 1;
+                }
+                Instr::Load(s) => {
+                    regs[top] = regs[s];
+                    top += 1;
+                }
+                Instr::Store(s) => {
+                    top -= 1;
+                    regs[s] = regs[top];
+                }
+                Instr::Neg => regs[top - 1] = -regs[top - 1],
+                Instr::Bin(op) => {
+                    top -= 1;
+                    regs[top - 1] = binary(op, regs[top - 1], regs[top]);
+                }
+                Instr::Member(c) => regs[top - 1] = splat(component(regs[top - 1], c)),
+                Instr::Call(f, argc) => {
+                    let at = top - argc;
+                    regs[at] = call(f, &regs[at..top], inputs);
+                    top = at + 1;
+                }
+                Instr::Ramp(r) => regs[top - 1] = self.ramps[r].sample(regs[top - 1].x),
+            }
+        }
+        let alpha = self.alpha_slot.map_or(1.0, |s| regs[s].x.clamp(0.0, 1.0));
+        (regs[self.color_slot], alpha)
+    }
+}
+
+fn read_input(i: Input, x: &ExprInputs) -> Vector3 {
+    let m = x.material;
+    match i {
+        Input::Position => x.position,
+        Input::Normal => x.normal,
+        Input::World => x.world,
+        Input::Uv => Vector3::new(x.uv.x, x.uv.y, 0.0),
+        Input::Frag => Vector3::new(x.frag.x, x.frag.y, 0.0),
+        Input::Time => splat(x.time),
+        Input::Resolution => Vector3::new(x.resolution.x, x.resolution.y, 0.0),
+        Input::Base => x.base,
+        Input::Pal1A => m.pal1_a, Input::Pal1B => m.pal1_b, Input::Pal1C => m.pal1_c, Input::Pal1D => m.pal1_d,
+        Input::Pal2A => m.pal2_a, Input::Pal2B => m.pal2_b, Input::Pal2C => m.pal2_c, Input::Pal2D => m.pal2_d,
+        Input::PalMixRadius => splat(m.pal_mix_radius),
+        Input::CraterFloorDark => splat(m.crater_floor_dark),
+        Input::CraterRayBright => splat(m.crater_ray_bright),
+    }
+}
+
+fn binary(op: char, a: Vector3, b: Vector3) -> Vector3 {
+    match op {
+        '+' => a + b,
+        '-' => a - b,
+        '*' => zip(a, b, |x, y| x * y),
+        _ => zip(a, b, |x, y| if y != 0.0 { x / y } else { 0.0 }),
+    }
+}
+
+fn call(f: Func, args: &[Vector3], x: &ExprInputs) -> Vector3 {
+    let seed = x.material.seed;
+    let octaves = |k: usize, default: u32| args.get(k).map_or(default, |v| octave_count(v.x));
+    match f {
+        Func::Noise => splat(noise3(NoiseBasis::Perlin, args[0], 1, 2.0, 0.5, seed)),
+        Func::Fbm => splat(noise3(NoiseBasis::Perlin, args[0], octaves(1, 5), 2.0, 0.5, seed)),
+        Func::Ridged => splat(noise3(NoiseBasis::Ridged, args[0], octaves(1, 5), 2.0, 0.5, seed)),
+        Func::Billow => splat(noise3(NoiseBasis::Billow, args[0], octaves(1, 5), 2.0, 0.5, seed)),
+        Func::Simplex => splat(noise3(NoiseBasis::Simplex, args[0], octaves(1, 5), 2.0, 0.5, seed)),
+        Func::Worley => {
+            let (f1, f2) = worley_noise3(args[0], seed);
+            Vector3::new(f1, f2, f2 - f1)
+        }
+        Func::Warp => {
+            let strength = args.get(1).map_or(1.0, |v| v.x);
+            splat(noise3(NoiseBasis::DomainWarp { strength }, args[0], 5, 2.0, 0.5, seed))
+        }
+        Func::MaterialPalette => x.material.palette1(args[0].x),
+        Func::MaterialPalette2 => x.material.palette2(args[0].x),
+        Func::Rings => splat(pattern_rings(xy(args[0]), args[1].x, args[2].x, x.time)),
+        Func::Stripes => splat(pattern_stripes(xy(args[0]), args[1].x.to_radians(), args[2].x, args[3].x, x.time)),
+        Func::Checker => splat(pattern_checker(xy(args[0]), args[1].x, 0.0)),
+        _ => call_pure(f, args),
+    }
+}
+
+fn call_pure(f: Func, a: &[Vector3]) -> Vector3 {
+    match f {
+        Func::Sin => each(a[0], f32::sin),
+        Func::Cos => each(a[0], f32::cos),
+        Func::Abs => each(a[0], f32::abs),
+        Func::Floor => each(a[0], f32::floor),
+        Func::Fract => each(a[0], |v| v - v.floor()),
+        Func::Sqrt => each(a[0], |v| v.max(0.0).sqrt()),
+        Func::Exp => each(a[0], f32::exp),
+        Func::Normalize => if a[0].length() > 0.0 { a[0].normalized() } else { a[0] },
+        Func::Length => splat(a[0].length()),
+        Func::Pow => zip(a[0], a[1], |x, y| x.max(0.0).powf(y)),
+        Func::Min => zip(a[0], a[1], f32::min),
+        Func::Max => zip(a[0], a[1], f32::max),
+        Func::Dot => splat(a[0].dot(a[1])),
+        Func::Step => zip(a[0], a[1], |edge, v| if v < edge { 0.0 } else { 1.0 }),
+        Func::Cross => a[0].cross(a[1]),
+        Func::Mix => mix3(a[0], a[1], a[2]),
+        Func::Clamp => clamp3(a[0], a[1], a[2]),
+        Func::Smoothstep => smoothstep3(a[0], a[1], a[2]),
+        Func::Vec3 => if a.len() == 3 { Vector3::new(a[0].x, a[1].x, a[2].x) } else { a[0] },
+        Func::Palette => palette_cosine(a[0].x, a[1], a[2], a[3], a[4]),
+        _ => Vector3::zero(), // inputs-dependent functions are handled in `call`
+    }
+}
+
+/// Octave argument as a count in 1..=MAX_OCTAVES (NaN counts as 1)
+fn octave_count(v: f32) -> u32 {
+    (v.clamp(1.0, MAX_OCTAVES as f32) as u32).max(1)
+}
+
+#[inline]
+fn component(v: Vector3, c: usize) -> f32 {
+    match c {
+        0 => v.x,
+        1 => v.y,
+        _ => v.z,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(program: &ExprProgram, position: Vector3) -> (Vector3, f32) {
+        let material = Material::default();
+        program.eval(&ExprInputs {
+            position,
+            normal: Vector3::new(0.0, 1.0, 0.0),
+            world: position,
+            uv: Vector2::zero(),
+            frag: Vector2::zero(),
+            time: 0.0,
+            resolution: Vector2::new(800.0, 600.0),
+            base: Vector3::zero(),
+            material: &material,
+        })
+    }
+
+    #[test]
+    fn valid_program() {
+        let program = ExprProgram::parse("k = p.x * 2 + 1\ncolor = vec3(k, p.y, -p.z); alpha = clamp(k, 0, 0.5)").unwrap();
+        let (color, alpha) = run(&program, Vector3::new(1.0, 2.0, 3.0));
+        assert_eq!(color, Vector3::new(3.0, 2.0, -3.0));
+        assert_eq!(alpha, 0.5);
+    }
+
+    #[test]
+    fn alpha_defaults_to_one() {
+        let program = ExprProgram::parse("color = mix(vec3(0), vec3(1), 0.25)").unwrap();
+        assert_eq!(run(&program, Vector3::zero()), (Vector3::new(0.25, 0.25, 0.25), 1.0));
+    }
+
+    #[test]
+    fn unknown_variable() {
+        let err = ExprProgram::parse("a = 1\ncolor = a + b").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 13));
+        assert_eq!(err.message, "unknown variable `b`");
+    }
+
+    #[test]
+    fn variable_used_before_assignment() {
+        let err = ExprProgram::parse("color = k\nk = 1").unwrap_err();
+        assert_eq!((err.line, err.column, err.message.as_str()), (1, 9, "unknown variable `k`"));
+    }
+
+    #[test]
+    fn wrong_argument_count() {
+        let err = ExprProgram::parse("color = vec3(1)\ncolor = mix(color, p)").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 9));
+        assert_eq!(err.message, "`mix` takes 3 argument(s), got 2");
+
+        let err = ExprProgram::parse("color = vec3(1, 2)").unwrap_err();
+        assert_eq!(err.message, "`vec3` takes 1 or 3 argument(s), got 2");
+    }
+
+    #[test]
+    fn missing_color() {
+        let err = ExprProgram::parse("alpha = 1\nk = 2\n").unwrap_err();
+        assert_eq!((err.line, err.column), (3, 1));
+        assert_eq!(err.message, "the program never assigns `color`");
+    }
+
+    #[test]
+    fn ramp_hits_its_stops_in_every_mode() {
+        for name in ["ramp", "ramp_linear", "ramp_smooth"] {
+            let src = format!("color = {}(p.x, 0, vec3(0), 0.25, vec3(1, 0, 0), 1, vec3(0, 0, 1))", name);
+            let program = ExprProgram::parse(&src).unwrap();
+            for (t, expected) in [(0.0, Vector3::zero()), (0.25, Vector3::new(1.0, 0.0, 0.0)), (1.0, Vector3::new(0.0, 0.0, 1.0))] {
+                let (c, _) = run(&program, Vector3::new(t, 0.0, 0.0));
+                assert!((c - expected).length() < 1e-4, "{} at {}: {:?}", name, t, c);
+            }
+        }
+        let linear = ExprProgram::parse("color = ramp_linear(p.x, 0, vec3(0), 1, vec3(1))").unwrap();
+        let smooth = ExprProgram::parse("color = ramp_smooth(p.x, 0, vec3(0), 1, vec3(1))").unwrap();
+        assert!((run(&linear, Vector3::new(0.25, 0.0, 0.0)).0.x - 0.25).abs() < 1e-6);
+        assert!((run(&smooth, Vector3::new(0.25, 0.0, 0.0)).0.x - 0.15625).abs() < 1e-6);
+    }
+
+    #[test]
+    fn ramp_stops_must_be_constant() {
+        let err = ExprProgram::parse("color = ramp(p.x, 0, p, 1, vec3(1))").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 9));
+        assert_eq!(err.message, "`ramp` stop positions and colors must be constant");
+        let err = ExprProgram::parse("color = ramp(p.x, 0, vec3(1))\ncolor = ramp(p.x, 0)").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 9));
+    }
+
+    #[test]
+    fn octave_count_is_clamped() {
+        assert_eq!(octave_count(1e9), MAX_OCTAVES);
+        assert_eq!(octave_count(f32::INFINITY), MAX_OCTAVES);
+        assert_eq!((octave_count(-3.0), octave_count(0.0), octave_count(f32::NAN)), (1, 1, 1));
+        assert_eq!(octave_count(4.7), 4);
+
+        let p = Vector3::new(0.3, 1.7, -2.2);
+        let huge = ExprProgram::parse("color = vec3(fbm(p, 1e9))").unwrap();
+        let max = ExprProgram::parse(&format!("color = vec3(fbm(p, {}))", MAX_OCTAVES)).unwrap();
+        assert_eq!(run(&huge, p), run(&max, p));
+    }
+
+    #[test]
+    fn syntax_errors_point_at_the_token() {
+        let err = ExprProgram::parse("color = (1 + 2\n").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 1));
+        let err = ExprProgram::parse("color = 1 $ 2").unwrap_err();
+        assert_eq!((err.line, err.column, err.message.as_str()), (1, 11, "unexpected character `$`"));
+        assert_eq!(err.to_string(), "1:11: unexpected character `$`");
+    }
+}
@@ -6,6 +6,19 @@ pub struct Fragment {
     pub position: Vector3,
     pub color: Vector3,
     pub depth: f32,
+    pub obj_pos: Vector3, // interpolated object-space position
+    pub world_pos: Vector3,
+    pub normal: Vector3,  // interpolated world-space normal (not renormalized)
+    pub uv: Vector2,
+    pub tangent: Vector3,
+    pub bitangent: Vector3,
+    // Change per pixel step in screen x / y (constant over a triangle), for mip selection
+    pub uv_dx: Vector2,
+    pub uv_dy: Vector2,
+    pub dp_dx: Vector3,   // of obj_pos
+    pub dp_dy: Vector3,
+    pub light_diffuse: Vector3,  // interpolated vertex lighting (ShadingMode::Gouraud only)
+    pub light_specular: Vector3,
 }
 
 impl Fragment {
@@ -14,6 +27,18 @@ impl Fragment {
             position: Vector3::new(x, y, depth),
             color,
             depth,
+            obj_pos: Vector3::zero(),
+            world_pos: Vector3::zero(),
+            normal: Vector3::zero(),
+            uv: Vector2::zero(),
+            tangent: Vector3::zero(),
+            bitangent: Vector3::zero(),
+            uv_dx: Vector2::zero(),
+            uv_dy: Vector2::zero(),
+            dp_dx: Vector3::zero(),
+            dp_dy: Vector3::zero(),
+            light_diffuse: Vector3::zero(),
+            light_specular: Vector3::zero(),
         }
     }
 }
\ No newline at end of file
@@ -43,10 +43,47 @@ impl Framebuffer {
             
         }
     }
+    /// Adds `color` on top of what is already in the buffer (depth-tested, depth not written),
+    /// for glows and other transparent shells
+    pub fn add_pixel(&mut self, x: u32, y: u32, depth: f32, color: Color) {
+        if x < self.width && y < self.height && depth < self.depth_buffer[(y*self.width + x) as usize] {
+            let dst = self.color_buffer.get_color(x as i32, y as i32);
+            let sum = Color::new(
+                dst.r.saturating_add(color.r),
+                dst.g.saturating_add(color.g),
+                dst.b.saturating_add(color.b),
+                255,
+            );
+            self.color_buffer.draw_pixel(x as i32, y as i32, sum);
+        }
+    }
+
+    /// Blends `color` over what is already in the buffer with coverage `alpha` (depth-tested, depth not written)
+    pub fn blend_pixel(&mut self, x: u32, y: u32, depth: f32, color: Color, alpha: f32) {
+        if x < self.width && y < self.height && depth < self.depth_buffer[(y*self.width + x) as usize] {
+            let dst = self.color_buffer.get_color(x as i32, y as i32);
+            let a = alpha.clamp(0.0, 1.0);
+            let mix = |d: u8, s: u8| (d as f32 * (1.0 - a) + s as f32 * a).round() as u8;
+            let out = Color::new(mix(dst.r, color.r), mix(dst.g, color.g), mix(dst.b, color.b), 255);
+            self.color_buffer.draw_pixel(x as i32, y as i32, out);
+        }
+    }
+
     pub fn get_color(&mut self, x: u32, y: u32) {
         self.color_buffer.get_color(x as i32, y as i32);
     }
 
+    /// Paints every pixel not covered by geometry (depth still at infinity) with `shade(x, y)`
+    pub fn fill_background<F: Fn(u32, u32) -> Color>(&mut self, shade: F) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if self.depth_buffer[(y*self.width + x) as usize] == f32::INFINITY {
+                    self.color_buffer.draw_pixel(x as i32, y as i32, shade(x, y));
+                }
+            }
+        }
+    }
+
     pub fn set_background_color(&mut self, color: Color) {
         self.background_color = color;
     }
@@ -0,0 +1,149 @@
+// src/gas_giant.rs
+use raylib::prelude::*;
+
+use crate::procedural::{hash_to_unit, hash_u32, mix, noise3, smoothstep, warped_fbm3, NoiseBasis};
+
+/// Gas giant shading: latitude zones/belts with differential rotation, sheared
+/// turbulence from domain-warped noise, and seeded oval storms drifting with their band.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GasGiantParams {
+    // Zones (bright) and belts (dark)
+    pub band_count: f32,          // zone+belt pairs from pole to pole
+    pub band_wobble: f32,         // low-frequency waviness of band edges (radians of latitude)
+    pub zone_color: Vector3,
+    pub belt_color: Vector3,
+    pub polar_color: Vector3,     // hazy caps blended in toward the poles
+    pub band_tint: f32,           // per-band random tint strength
+
+    // Differential rotation (rad/s of longitude): base + jets alternating with latitude
+    pub base_rotation: f32,
+    pub jet_speed: f32,
+    pub jet_count: f32,
+
+    // Shear turbulence (domain-warped FBM, stretched along longitude)
+    pub turb_freq: f32,
+    pub turb_strength: f32,       // latitude displacement of band lookup
+    pub turb_warp: f32,
+    pub turb_octaves: u32,
+    pub turb_stretch: f32,        // >1: eddies elongated east-west
+
+    // Storms
+    pub storm_count: u32,
+    pub storm_size: f32,          // typical semi-major axis (radians of longitude)
+    pub storm_color: Vector3,     // small white ovals
+    pub great_spot: bool,         // first storm becomes a large red oval
+    pub great_spot_color: Vector3,
+    pub storm_spin: f32,          // internal swirl speed
+}
+
+impl GasGiantParams {
+    pub fn jovian() -> Self {
+        Self {
+            band_count: 7.0,
+            band_wobble: 0.035,
+            zone_color: Vector3::new(0.92, 0.86, 0.74),
+            belt_color: Vector3::new(0.62, 0.44, 0.30),
+            polar_color: Vector3::new(0.55, 0.52, 0.50),
+            band_tint: 0.12,
+            base_rotation: 0.05,
+            jet_speed: 0.04,
+            jet_count: 9.0,
+            turb_freq: 3.0,
+            turb_strength: 0.06,
+            turb_warp: 1.3,
+            turb_octaves: 4,
+            turb_stretch: 3.0,
+            storm_count: 7,
+            storm_size: 0.16,
+            storm_color: Vector3::new(0.96, 0.94, 0.90),
+            great_spot: true,
+            great_spot_color: Vector3::new(0.78, 0.38, 0.24),
+            storm_spin: 0.6,
+        }
+    }
+}
+
+/// Angular rotation speed of the atmosphere at a latitude (radians)
+fn rotation_speed(g: &GasGiantParams, lat: f32) -> f32 {
+    g.base_rotation + g.jet_speed * (lat * g.jet_count).cos()
+}
+
+/// Color of the cloud tops at an object-space point and time.
+pub fn gas_giant_color(p: Vector3, g: &GasGiantParams, seed: u32, time: f32) -> Vector3 {
+    let dir = p.normalized();
+    let lat = dir.y.clamp(-1.0, 1.0).asin();  // -PI/2..PI/2
+    let lon = dir.z.atan2(dir.x);              // -PI..PI
+
+    // Differential rotation: every latitude advects at its own speed, which shears the noise
+    let lon_t = lon - rotation_speed(g, lat) * time;
+    let (cl, sl) = (lat.cos(), lat.sin());
+    let q = Vector3::new(cl * lon_t.cos(), sl, cl * lon_t.sin());
+
+    // Turbulence stretched east-west; pushes the band lookup up/down in latitude
+    let tq = Vector3::new(q.x * g.turb_freq, q.y * g.turb_freq * g.turb_stretch, q.z * g.turb_freq);
+    let turb = warped_fbm3(tq, g.turb_octaves, 2.0, 0.5, g.turb_warp, seed);
+    let wobble = noise3(NoiseBasis::Perlin, q * 1.5, 2, 2.0, 0.5, seed ^ 0x6A5_0001) * g.band_wobble;
+
+    // Storms deflect the bands around them and paint their own ovals
+    let mut storm_mix = 0.0;
+    let mut storm_col = Vector3::zero();
+    let mut deflect = 0.0;
+    for s in 0..g.storm_count {
+        let (slat, slon, a, b, color) = storm(g, seed, s, time);
+        let mut dlon = lon - slon;
+        dlon = (dlon + std::f32::consts::PI).rem_euclid(std::f32::consts::TAU) - std::f32::consts::PI;
+        let x = dlon * lat.cos() / a;
+        let y = (lat - slat) / b;
+        let r = (x * x + y * y).sqrt();
+        if r < 2.0 {
+            // Bands flow around the oval: push latitude away from its center
+            deflect += y.signum() * b * 0.5 * (1.0 - smoothstep(0.8, 2.0, r));
+        }
+        if r < 1.0 {
+            // Swirl: spiral arms rotating inside the oval, darker core, bright collar
+            let ang = y.atan2(x) + r * 4.0 - time * g.storm_spin;
+            let spiral = 0.5 + 0.5 * (ang * 2.0).sin();
+            let collar = smoothstep(0.7, 0.95, r) * (1.0 - smoothstep(0.95, 1.0, r));
+            let inside = 1.0 - smoothstep(0.85, 1.0, r);
+            let c = color * (0.85 + 0.15 * spiral) + Vector3::one() * (0.12 * collar);
+            if inside > storm_mix {
+                storm_mix = inside;
+                storm_col = c;
+            }
+        }
+    }
+
+    // Zones and belts from the perturbed latitude
+    let lat_b = lat + wobble + turb * g.turb_strength + deflect;
+    let band = 0.5 + 0.5 * (lat_b * g.band_count * 2.0).cos();
+    let band_sharp = smoothstep(0.3, 0.7, band);
+    let mut col = mix(g.belt_color, g.zone_color, band_sharp);
+
+    // Each band gets its own slight tint so neighbors are not identical
+    let band_id = (lat_b * g.band_count * 2.0 / std::f32::consts::PI).floor() as i32;
+    let h = hash_u32(seed ^ (band_id as u32).wrapping_mul(0x9E37_79B9));
+    let tint = Vector3::new(hash_to_unit(h), hash_to_unit(hash_u32(h)), hash_to_unit(hash_u32(h ^ 7))) - Vector3::new(0.5, 0.5, 0.5);
+    col += tint * g.band_tint;
+
+    // Fine turbulent texture and hazy poles
+    col = col * (0.9 + 0.2 * (turb * 0.5 + 0.5));
+    col = mix(col, g.polar_color, smoothstep(0.9, 1.35, lat.abs()));
+
+    mix(col, storm_col, storm_mix)
+}
+
+/// Seeded storm `s`: (latitude, longitude now, semi-axis lon, semi-axis lat, color)
+fn storm(g: &GasGiantParams, seed: u32, s: u32, time: f32) -> (f32, f32, f32, f32, Vector3) {
+    let mut h = hash_u32(seed ^ 0x5707_0000 ^ s.wrapping_mul(0x85EB_CA6B));
+    let mut next = || { h = hash_u32(h); hash_to_unit(h) };
+    let great = g.great_spot && s == 0;
+
+    let lat = if great { -0.38 } else { (next() - 0.5) * 2.2 };
+    let lon0 = next() * std::f32::consts::TAU;
+    let size = if great { g.storm_size * 2.2 } else { g.storm_size * (0.35 + 0.65 * next()) };
+    // Storms drift with their band plus a small own velocity
+    let drift = rotation_speed(g, lat) + (next() - 0.5) * 0.02;
+    let lon = lon0 + drift * time;
+    let color = if great { g.great_spot_color } else { g.storm_color };
+    (lat, lon, size, size * 0.55, color)
+}
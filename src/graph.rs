@@ -0,0 +1,434 @@
+// src/graph.rs
+use raylib::prelude::*;
+use std::collections::HashMap;
+
+use crate::layers::{composite, LayerBlend};
+use crate::procedural::{clamp3, each, fbm3, mix3, noise3, smoothstep3, splat, xy, zip, zip3, NoiseBasis, MAX_OCTAVES};
+use crate::ramp::ColorRamp;
+use crate::shaders::{palette_cosine, pattern_checker, pattern_rings, pattern_stripes};
+
+/// Node-based material loaded from a text file, evaluated per fragment as a `LayerKind::Graph`.
+///
+/// One node per line, `id = type inputs... key=value...`, `#` starts a comment:
+///
+/// ```text
+/// h   = noise position freq=3 octaves=6 basis=ridged
+/// t   = remap h -1 1 0 1
+/// col = palette t a=0.5,0.4,0.3 b=0.4,0.3,0.2 d=0,0.1,0.2
+/// out = output col
+/// ```
+///
+/// Inputs are earlier node ids, the built-ins `position` `normal` `uv` `time`, or literals
+/// (`0.5` or `1,0.5,0`). Every value is a vec3; scalars are stored in all three components.
+/// `ramp` is the stop-based alternative to `palette`: `ramp t stops=0:0.1,0.2,0.5;1:1 mode=oklab`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MaterialGraph {
+    nodes: Vec<Node>,
+    color: Input,
+    alpha: Option<Input>,
+}
+
+/// What the graph can read at a fragment
+pub struct GraphInputs {
+    pub position: Vector3, // object space
+    pub normal: Vector3,   // world space
+    pub uv: Vector2,       // screen space, [-.5, .5]
+    pub time: f32,
+    pub seed: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Input {
+    Node(usize),
+    Builtin(Builtin),
+    Const(Vector3),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Builtin {
+    Position,
+    Normal,
+    Uv,
+    Time,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Op {
+    Noise { basis: NoiseBasis, freq: f32, octaves: u32, lacunarity: f32, gain: f32, seed: u32 },
+    Fbm { freq: f32, octaves: u32, lacunarity: f32, gain: f32, seed: u32 },
+    Rings { freq: f32, speed: f32 },
+    Stripes { angle_deg: f32, freq: f32, speed: f32 },
+    Checker { scale: f32, speed: f32 },
+    Palette { a: Vector3, b: Vector3, c: Vector3, d: Vector3 },
+    Ramp(ColorRamp),
+    Blend { mode: LayerBlend },
+    Remap,
+    Math(MathOp),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum MathOp {
+    Add, Sub, Mul, Div, Min, Max, Pow, Dot,
+    Abs, Sin, Cos, Fract, Floor, Sqrt, Length,
+    Mix, Clamp, Smoothstep,
+}
+
+/// Node count limit; `eval` keeps every node's value in a fixed array
+const MAX_NODES: usize = 64;
+
+#[derive(Clone, Debug, PartialEq)]
+struct Node {
+    op: Op,
+    inputs: Vec<Input>,
+}
+
+impl MaterialGraph {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let src = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Self::parse(&src).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    /// Parses the text format; errors name the offending line
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let mut ids: HashMap<String, usize> = HashMap::new();
+        let mut nodes = Vec::new();
+        let mut output = None;
+
+        for (i, raw) in src.lines().enumerate() {
+            let line = raw.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let fail = |msg: String| format!("line {}: {}", i + 1, msg);
+
+            let (id, rest) = line.split_once('=').ok_or_else(|| fail("expected `id = type ...`".into()))?;
+            let id = id.trim();
+            if !is_identifier(id) || builtin(id).is_some() {
+                return Err(fail(format!("`{}` is not a valid node id", id)));
+            }
+            if ids.contains_key(id) {
+                return Err(fail(format!("node `{}` is defined twice", id)));
+            }
+
+            let mut words = rest.split_whitespace();
+            let kind = words.next().ok_or_else(|| fail("missing node type".into()))?;
+            let mut args = Vec::new();
+            let mut params = Params::default();
+            for word in words {
+                match word.split_once('=') {
+                    Some((k, v)) => params.values.push((k.to_string(), v.to_string(), false)),
+                    None => args.push(parse_input(word, &ids).map_err(fail)?),
+                }
+            }
+
+            if kind == "output" {
+                if output.is_some() {
+                    return Err(fail("only one output node is allowed".into()));
+                }
+                if !(1..=2).contains(&args.len()) {
+                    return Err(fail("output takes a color and an optional alpha".into()));
+                }
+                output = Some((args[0], args.get(1).copied()));
+                continue;
+            }
+
+            let (op, arity) = parse_op(kind, &mut params).map_err(fail)?;
+            if args.len() != arity {
+                return Err(fail(format!("`{}` takes {} input(s), got {}", kind, arity, args.len())));
+            }
+            if let Some(unused) = params.unused() {
+                return Err(fail(format!("`{}` has no parameter `{}`", kind, unused)));
+            }
+            if nodes.len() == MAX_NODES {
+                return Err(fail(format!("too many nodes (at most {})", MAX_NODES)));
+            }
+            ids.insert(id.to_string(), nodes.len());
+            nodes.push(Node { op, inputs: args });
+        }
+
+        let (color, alpha) = output.ok_or("no `output` node")?;
+        Ok(MaterialGraph { nodes, color, alpha })
+    }
+
+    /// Color and coverage (alpha, 1 if the output has none) at one fragment
+    pub fn eval(&self, inputs: &GraphInputs) -> (Vector3, f32) {
+        let mut values = [Vector3::zero(); MAX_NODES];
+        for (i, node) in self.nodes.iter().enumerate() {
+            let arg = |k: usize| fetch(node.inputs[k], &values, inputs);
+            let v = match node.op {
+                Op::Noise { basis, freq, octaves, lacunarity, gain, seed } =>
+                    splat(noise3(basis, arg(0) * freq, octaves, lacunarity, gain, inputs.seed ^ seed)),
+                Op::Fbm { freq, octaves, lacunarity, gain, seed } =>
+                    splat(fbm3(arg(0) * freq, octaves, lacunarity, gain, inputs.seed ^ seed)),
+                Op::Rings { freq, speed } => splat(pattern_rings(xy(arg(0)), freq, speed, inputs.time)),
+                Op::Stripes { angle_deg, freq, speed } =>
+                    splat(pattern_stripes(xy(arg(0)), angle_deg.to_radians(), freq, speed, inputs.time)),
+                Op::Checker { scale, speed } => splat(pattern_checker(xy(arg(0)), scale, inputs.time * speed)),
+                Op::Palette { a, b, c, d } => palette_cosine(arg(0).x, a, b, c, d),
+                Op::Ramp(ref ramp) => ramp.sample(arg(0).x),
+                Op::Blend { mode } => composite(arg(0), arg(1), mode, arg(2).x),
+                Op::Remap => {
+                    let (x, in_lo, in_hi, out_lo, out_hi) = (arg(0), arg(1), arg(2), arg(3), arg(4));
+                    zip3(zip3(x, in_lo, in_hi, |x, a, b| if b != a { (x - a) / (b - a) } else { 0.0 }), out_lo, out_hi, |t, a, b| a + (b - a) * t)
+                }
+                Op::Math(m) => math(m, &node.inputs, &values, inputs),
+            };
+            values[i] = v;
+        }
+        let color = fetch(self.color, &values, inputs);
+        let alpha = self.alpha.map_or(1.0, |a| fetch(a, &values, inputs).x.clamp(0.0, 1.0));
+        (color, alpha)
+    }
+}
+
+fn math(m: MathOp, args: &[Input], values: &[Vector3], inputs: &GraphInputs) -> Vector3 {
+    let arg = |k: usize| fetch(args[k], values, inputs);
+    match m {
+        MathOp::Add => arg(0) + arg(1),
+        MathOp::Sub => arg(0) - arg(1),
+        MathOp::Mul => zip(arg(0), arg(1), |a, b| a * b),
+        MathOp::Div => zip(arg(0), arg(1), |a, b| if b != 0.0 { a / b } else { 0.0 }),
+        MathOp::Min => zip(arg(0), arg(1), f32::min),
+        MathOp::Max => zip(arg(0), arg(1), f32::max),
+        MathOp::Pow => zip(arg(0), arg(1), |a, b| a.max(0.0).powf(b)),
+        MathOp::Dot => splat(arg(0).dot(arg(1))),
+        MathOp::Abs => each(arg(0), f32::abs),
+        MathOp::Sin => each(arg(0), f32::sin),
+        MathOp::Cos => each(arg(0), f32::cos),
+        MathOp::Fract => each(arg(0), |a| a - a.floor()),
+        MathOp::Floor => each(arg(0), f32::floor),
+        MathOp::Sqrt => each(arg(0), |a| a.max(0.0).sqrt()),
+        MathOp::Length => splat(arg(0).length()),
+        MathOp::Mix => mix3(arg(0), arg(1), arg(2)),
+        MathOp::Clamp => clamp3(arg(0), arg(1), arg(2)),
+        MathOp::Smoothstep => smoothstep3(arg(0), arg(1), arg(2)),
+    }
+}
+
+/// Node type -> operation and number of positional inputs, consuming its `key=value` params
+fn parse_op(kind: &str, p: &mut Params) -> Result<(Op, usize), String> {
+    let math = |m: MathOp, n: usize| Ok((Op::Math(m), n));
+    match kind {
+        "noise" => {
+            let basis = match p.word("basis", "perlin")?.as_str() {
+                "value" => NoiseBasis::Value,
+                "perlin" => NoiseBasis::Perlin,
+                "simplex" => NoiseBasis::Simplex,
+                "worley" => NoiseBasis::Worley,
+                "ridged" => NoiseBasis::Ridged,
+                "billow" => NoiseBasis::Billow,
+                "warp" => NoiseBasis::DomainWarp { strength: p.float("warp", 1.0)? },
+                other => return Err(format!("unknown noise basis `{}`", other)),
+            };
+            Ok((Op::Noise {
+                basis,
+                freq: p.float("freq", 1.0)?,
+                octaves: p.octaves(5)?,
+                lacunarity: p.float("lacunarity", 2.0)?,
+                gain: p.float("gain", 0.5)?,
+                seed: p.float("seed", 0.0)? as u32,
+            }, 1))
+        }
+        "fbm" => Ok((Op::Fbm {
+            freq: p.float("freq", 1.0)?,
+            octaves: p.octaves(5)?,
+            lacunarity: p.float("lacunarity", 2.0)?,
+            gain: p.float("gain", 0.5)?,
+            seed: p.float("seed", 0.0)? as u32,
+        }, 1)),
+        "rings" => Ok((Op::Rings { freq: p.float("freq", 12.0)?, speed: p.float("speed", 0.0)? }, 1)),
+        "stripes" => Ok((Op::Stripes {
+            angle_deg: p.float("angle", 0.0)?,
+            freq: p.float("freq", 18.0)?,
+            speed: p.float("speed", 0.0)?,
+        }, 1)),
+        "checker" => Ok((Op::Checker { scale: p.float("scale", 6.0)?, speed: p.float("speed", 0.0)? }, 1)),
+        "palette" => Ok((Op::Palette {
+            a: p.vec("a", splat(0.5))?,
+            b: p.vec("b", splat(0.5))?,
+            c: p.vec("c", splat(1.0))?,
+            d: p.vec("d", Vector3::new(0.0, 0.33, 0.67))?,
+        }, 1)),
+        "ramp" => {
+            let mode = p.word("mode", "linear")?;
+            let mode = ColorRamp::mode_named(&mode).ok_or_else(|| format!("unknown ramp mode `{}`", mode))?;
+            let stops = p.take("stops").ok_or("ramp needs `stops=pos:r,g,b;pos:r,g,b;...`")?;
+            Ok((Op::Ramp(ColorRamp::parse(mode, &stops)?), 1))
+        }
+        "blend" => {
+            let mode = match p.word("mode", "normal")?.as_str() {
+                "normal" => LayerBlend::Normal,
+                "multiply" => LayerBlend::Multiply,
+                "screen" => LayerBlend::Screen,
+                "overlay" => LayerBlend::Overlay,
+                "add" => LayerBlend::Add,
+                other => return Err(format!("unknown blend mode `{}`", other)),
+            };
+            Ok((Op::Blend { mode }, 3))
+        }
+        "remap" => Ok((Op::Remap, 5)),
+        "add" => math(MathOp::Add, 2),
+        "sub" => math(MathOp::Sub, 2),
+        "mul" => math(MathOp::Mul, 2),
+        "div" => math(MathOp::Div, 2),
+        "min" => math(MathOp::Min, 2),
+        "max" => math(MathOp::Max, 2),
+        "pow" => math(MathOp::Pow, 2),
+        "dot" => math(MathOp::Dot, 2),
+        "abs" => math(MathOp::Abs, 1),
+        "sin" => math(MathOp::Sin, 1),
+        "cos" => math(MathOp::Cos, 1),
+        "fract" => math(MathOp::Fract, 1),
+        "floor" => math(MathOp::Floor, 1),
+        "sqrt" => math(MathOp::Sqrt, 1),
+        "length" => math(MathOp::Length, 1),
+        "mix" => math(MathOp::Mix, 3),
+        "clamp" => math(MathOp::Clamp, 3),
+        "smoothstep" => math(MathOp::Smoothstep, 3),
+        other => Err(format!("unknown node type `{}`", other)),
+    }
+}
+
+/// `key=value` params of one node; remembers which were read to flag typos
+#[derive(Default)]
+struct Params {
+    values: Vec<(String, String, bool)>,
+}
+
+impl Params {
+    fn take(&mut self, key: &str) -> Option<String> {
+        self.values.iter_mut().find(|(k, _, _)| k == key).map(|(_, v, used)| {
+            *used = true;
+            v.clone()
+        })
+    }
+    fn word(&mut self, key: &str, default: &str) -> Result<String, String> {
+        Ok(self.take(key).unwrap_or_else(|| default.to_string()))
+    }
+    fn float(&mut self, key: &str, default: f32) -> Result<f32, String> {
+        self.take(key).map_or(Ok(default), |v| v.parse().map_err(|_| format!("`{}={}` is not a number", key, v)))
+    }
+    fn octaves(&mut self, default: u32) -> Result<u32, String> {
+        let n = self.float("octaves", default as f32)?;
+        if !(1.0..=MAX_OCTAVES as f32).contains(&n) {
+            return Err(format!("`octaves={}` must be between 1 and {}", n, MAX_OCTAVES));
+        }
+        Ok(n as u32)
+    }
+    fn vec(&mut self, key: &str, default: Vector3) -> Result<Vector3, String> {
+        self.take(key).map_or(Ok(default), |v| parse_vec(&v).ok_or_else(|| format!("`{}={}` is not a number or x,y,z", key, v)))
+    }
+    fn unused(&self) -> Option<&str> {
+        self.values.iter().find(|(_, _, used)| !used).map(|(k, _, _)| k.as_str())
+    }
+}
+
+fn parse_input(word: &str, ids: &HashMap<String, usize>) -> Result<Input, String> {
+    if let Some(b) = builtin(word) {
+        return Ok(Input::Builtin(b));
+    }
+    if let Some(&i) = ids.get(word) {
+        return Ok(Input::Node(i));
+    }
+    if let Some(v) = parse_vec(word) {
+        return Ok(Input::Const(v));
+    }
+    Err(format!("unknown node `{}` (nodes must be defined before use)", word))
+}
+
+fn builtin(word: &str) -> Option<Builtin> {
+    match word {
+        "position" => Some(Builtin::Position),
+        "normal" => Some(Builtin::Normal),
+        "uv" => Some(Builtin::Uv),
+        "time" => Some(Builtin::Time),
+        _ => None,
+    }
+}
+
+/// `0.5` (splat) or `x,y,z`
+fn parse_vec(s: &str) -> Option<Vector3> {
+    let parts: Vec<f32> = s.split(',').map(|x| x.trim().parse().ok()).collect::<Option<_>>()?;
+    match parts[..] {
+        [v] => Some(splat(v)),
+        [x, y, z] => Some(Vector3::new(x, y, z)),
+        _ => None,
+    }
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn fetch(input: Input, values: &[Vector3], inputs: &GraphInputs) -> Vector3 {
+    match input {
+        Input::Node(i) => values[i],
+        Input::Const(v) => v,
+        Input::Builtin(Builtin::Position) => inputs.position,
+        Input::Builtin(Builtin::Normal) => inputs.normal,
+        Input::Builtin(Builtin::Uv) => Vector3::new(inputs.uv.x, inputs.uv.y, 0.0),
+        Input::Builtin(Builtin::Time) => splat(inputs.time),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(graph: &MaterialGraph, position: Vector3) -> (Vector3, f32) {
+        graph.eval(&GraphInputs { position, normal: Vector3::new(0.0, 1.0, 0.0), uv: Vector2::zero(), time: 0.0, seed: 0 })
+    }
+
+    #[test]
+    fn math_nodes() {
+        let graph = MaterialGraph::parse("a = add position 1\nb = mul a 0.5,2,-1\nout = output b 3").unwrap();
+        assert_eq!(run(&graph, Vector3::new(1.0, 2.0, 3.0)), (Vector3::new(1.0, 6.0, -4.0), 1.0));
+        let graph = MaterialGraph::parse("q = div position 0,2,0  # guarded\nout = output q").unwrap();
+        assert_eq!(run(&graph, Vector3::new(1.0, 4.0, 1.0)), (Vector3::new(0.0, 2.0, 0.0), 1.0));
+    }
+
+    #[test]
+    fn remap() {
+        let graph = MaterialGraph::parse("t = remap position -1 1 10 20\nout = output t").unwrap();
+        assert_eq!(run(&graph, Vector3::new(-1.0, 0.0, 1.0)).0, Vector3::new(10.0, 15.0, 20.0));
+        // Empty input range maps everything to the low end instead of dividing by zero
+        let graph = MaterialGraph::parse("t = remap position 2 2 10 20\nout = output t").unwrap();
+        assert_eq!(run(&graph, Vector3::new(2.0, 5.0, -3.0)).0, splat(10.0));
+    }
+
+    #[test]
+    fn noise_octaves_are_bounded() {
+        assert!(MaterialGraph::parse(&format!("h = fbm position octaves={}\nout = output h", MAX_OCTAVES)).is_ok());
+        let err = MaterialGraph::parse("\nh = noise position octaves=1e9\nout = output h").unwrap_err();
+        assert_eq!(err, format!("line 2: `octaves=1000000000` must be between 1 and {}", MAX_OCTAVES));
+        assert!(MaterialGraph::parse("h = fbm position octaves=0\nout = output h").unwrap_err().starts_with("line 1: `octaves=0`"));
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let cases = [
+            ("# header\n\nh = blur position\nout = output h", "line 3: unknown node type `blur`"),
+            ("h = sin k\nout = output h", "line 1: unknown node `k` (nodes must be defined before use)"),
+            ("h = noise position\nc = palette h frq=2\nout = output c", "line 2: `palette` has no parameter `frq`"),
+            ("h = add position\nout = output h", "line 1: `add` takes 2 input(s), got 1"),
+            ("h = sin position\nh = cos position", "line 2: node `h` is defined twice"),
+            ("time = sin position", "line 1: `time` is not a valid node id"),
+            ("h = noise position basis=cubic", "line 1: unknown noise basis `cubic`"),
+            ("h = sin position\nout = output h\nout2 = output h", "line 3: only one output node is allowed"),
+            ("h = sin position", "no `output` node"),
+        ];
+        for (src, expected) in cases {
+            assert_eq!(MaterialGraph::parse(src).unwrap_err(), expected, "{:?}", src);
+        }
+    }
+
+    #[test]
+    fn node_limit() {
+        let mut src: String = (0..MAX_NODES).map(|i| format!("n{} = sin position\n", i)).collect();
+        assert!(MaterialGraph::parse(&format!("{}out = output n0", src)).is_ok());
+        src.push_str("extra = sin position\n");
+        assert_eq!(MaterialGraph::parse(&src).unwrap_err(), format!("line {}: too many nodes (at most {})", MAX_NODES + 1, MAX_NODES));
+    }
+}
@@ -0,0 +1,158 @@
+// src/heightmap.rs
+use raylib::prelude::*;
+
+use crate::texture::equirect_uv;
+
+/// Grayscale equirectangular elevation image (e.g. a lunar or Martian DEM), values normalized to 0..1.
+/// Binary PGM (P5) keeps 16-bit samples; other formats go through raylib, which reads 8 bits per channel,
+/// so 16-bit PNGs are rejected rather than silently losing their low byte.
+#[derive(Clone, Debug)]
+pub struct Heightmap {
+    pub width: u32,
+    pub height: u32,
+    pub bits: u32,     // precision of the source samples (8 or 16)
+    data: Vec<f32>,    // row-major, top row (north pole) first
+}
+
+impl Heightmap {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let is_pgm = path.rsplit('.').next().is_some_and(|ext| ext.eq_ignore_ascii_case("pgm"));
+        if is_pgm {
+            let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+            return parse_pgm(&bytes).map_err(|e| format!("{}: {}", path, e));
+        }
+        let is_png = path.rsplit('.').next().is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+        if is_png {
+            let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+            if png_bit_depth(&bytes) == Some(16) {
+                return Err(format!("{}: 16-bit PNG would be cut to 8 bits; convert it to a binary PGM", path));
+            }
+        }
+        let image = Image::load_image(path).map_err(|e| format!("{}: {}", path, e))?;
+        let (width, height) = (image.width() as u32, image.height() as u32);
+        let data = image.get_image_data().iter().map(|c| c.r as f32 / 255.0).collect();
+        Ok(Heightmap { width, height, bits: 8, data })
+    }
+
+    /// Bilinear elevation (0..1) under object-space direction `p`; wraps in longitude, clamps at the poles
+    pub fn sample(&self, p: Vector3) -> f32 {
+        let uv = equirect_uv(p);
+        let fx = uv.x * self.width as f32 - 0.5;
+        let fy = (1.0 - uv.y) * self.height as f32 - 0.5;
+        let (x0, y0) = (fx.floor(), fy.floor());
+        let (tx, ty) = (fx - x0, fy - y0);
+        let (x0, y0) = (x0 as i32, y0 as i32);
+        let at = |x: i32, y: i32| {
+            let xi = x.rem_euclid(self.width as i32) as u32;
+            let yi = y.clamp(0, self.height as i32 - 1) as u32;
+            self.data[(yi * self.width + xi) as usize]
+        };
+        let top = at(x0, y0) * (1.0 - tx) + at(x0 + 1, y0) * tx;
+        let bot = at(x0, y0 + 1) * (1.0 - tx) + at(x0 + 1, y0 + 1) * tx;
+        top * (1.0 - ty) + bot * ty
+    }
+}
+
+/// Binary PGM: "P5", width, height, maxval (whitespace and # comments between), then
+/// one byte per sample if maxval < 256, else two bytes big-endian
+fn parse_pgm(bytes: &[u8]) -> Result<Heightmap, String> {
+    let mut pos = 0;
+    let mut token = || -> Result<String, String> {
+        loop {
+            match bytes.get(pos) {
+                Some(b'#') => while bytes.get(pos).is_some_and(|&b| b != b'\n') { pos += 1; },
+                Some(b) if b.is_ascii_whitespace() => pos += 1,
+                Some(_) => break,
+                None => return Err("truncated header".into()),
+            }
+        }
+        let start = pos;
+        while bytes.get(pos).is_some_and(|b| !b.is_ascii_whitespace()) {
+            pos += 1;
+        }
+        Ok(String::from_utf8_lossy(&bytes[start..pos]).into_owned())
+    };
+
+    if token()? != "P5" {
+        return Err("not a binary PGM (P5)".into());
+    }
+    let mut number = || -> Result<u32, String> {
+        let t = token()?;
+        t.parse().map_err(|_| format!("bad header value {:?}", t))
+    };
+    let (width, height, maxval) = (number()?, number()?, number()?);
+    if width == 0 || height == 0 || maxval == 0 || maxval > 65535 {
+        return Err(format!("unsupported PGM {}x{} maxval {}", width, height, maxval));
+    }
+    pos += 1; // single whitespace before the raster
+
+    let wide = maxval > 255;
+    let too_large = || format!("PGM too large: {}x{}", width, height);
+    let count = (width as usize).checked_mul(height as usize).ok_or_else(too_large)?;
+    let size = count.checked_mul(if wide { 2 } else { 1 }).ok_or_else(too_large)?;
+    let raster = &bytes[pos.min(bytes.len())..];
+    if raster.len() < size {
+        return Err("truncated raster".into());
+    }
+    let data = if wide {
+        raster.chunks_exact(2).take(count).map(|b| u16::from_be_bytes([b[0], b[1]]) as f32 / maxval as f32).collect()
+    } else {
+        raster.iter().take(count).map(|&b| b as f32 / maxval as f32).collect()
+    };
+    Ok(Heightmap { width, height, bits: if wide { 16 } else { 8 }, data })
+}
+
+/// Bit depth from a PNG's IHDR chunk (always the first), or None if `bytes` isn't a PNG
+fn png_bit_depth(bytes: &[u8]) -> Option<u8> {
+    const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+    (bytes.starts_with(SIGNATURE) && bytes.get(12..16) == Some(b"IHDR")).then(|| bytes.get(24).copied()).flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pgm_8_bit() {
+        let map = parse_pgm(b"P5 2 2 255\n\x00\x40\x80\xff").unwrap();
+        assert_eq!((map.width, map.height, map.bits), (2, 2, 8));
+        assert_eq!(map.data, vec![0.0, 64.0 / 255.0, 128.0 / 255.0, 1.0]);
+    }
+
+    #[test]
+    fn pgm_16_bit_is_big_endian() {
+        let map = parse_pgm(b"P5\n2 1\n65535\n\x00\x01\xff\xff").unwrap();
+        assert_eq!((map.width, map.height, map.bits), (2, 1, 16));
+        assert_eq!(map.data, vec![1.0 / 65535.0, 1.0]);
+    }
+
+    #[test]
+    fn pgm_comments_between_header_fields() {
+        let map = parse_pgm(b"P5\n# exported by gdal\n1 # width\n1\n# max\n255\n\x80").unwrap();
+        assert_eq!((map.width, map.height), (1, 1));
+        assert_eq!(map.data, vec![128.0 / 255.0]);
+    }
+
+    #[test]
+    fn pgm_truncated() {
+        assert_eq!(parse_pgm(b"P5 2 2 255\n\x00\x01\x02").unwrap_err(), "truncated raster");
+        assert_eq!(parse_pgm(b"P5 2 1 65535\n\x00\x01\x02").unwrap_err(), "truncated raster");
+        assert_eq!(parse_pgm(b"P5 2 2").unwrap_err(), "truncated header");
+        assert!(parse_pgm(b"P2 1 1 255\n0").is_err());
+    }
+
+    #[test]
+    fn pgm_size_overflow() {
+        assert!(parse_pgm(b"P5 4294967295 4294967295 65535\n").unwrap_err().starts_with("PGM too large"));
+    }
+
+    #[test]
+    fn png_depth_from_header() {
+        let mut png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR\x00\x00\x00\x01\x00\x00\x00\x01".to_vec();
+        png.push(16);
+        assert_eq!(png_bit_depth(&png), Some(16));
+        png[24] = 8;
+        assert_eq!(png_bit_depth(&png), Some(8));
+        assert_eq!(png_bit_depth(b"P5 1 1 255\n\x00"), None);
+    }
+}
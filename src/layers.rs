@@ -0,0 +1,124 @@
+// src/layers.rs
+use raylib::prelude::*;
+use std::sync::Arc;
+
+use crate::expr::ExprProgram;
+use crate::graph::MaterialGraph;
+use crate::procedural::NoiseBasis;
+
+/// How a layer's color combines with the color below it (per channel, 0..1)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LayerBlend {
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    Add,
+}
+
+/// Screen-space stripes/rings/checker tinting toward an accent color
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BandsLayer {
+    pub rings_freq: f32, pub rings_speed: f32,
+    pub stripes_angle_deg: f32, pub stripes_freq: f32, pub stripes_speed: f32,
+    pub checker_scale: f32,
+    pub rings_weight: f32, pub stripes_weight: f32, pub checker_weight: f32,
+    pub accent: Vector3, pub accent_strength: f32,
+}
+
+/// Animated screen-space FBM; the layer color is the factor (sat_base + sat_amp*f) * (bri_base + bri_amp*f)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NoiseLayer {
+    pub freq: f32, pub octaves: u32, pub lacunarity: f32, pub gain: f32, pub time: f32,
+    pub basis: NoiseBasis,
+    pub sat_base: f32, pub sat_amp: f32,
+    pub bri_base: f32, pub bri_amp: f32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LayerKind {
+    Surface,                     // the material's own albedo (star, terrain, gas, rings, palettes, ...)
+    Bands(BandsLayer),
+    Noise(NoiseLayer),
+    Scanlines { intensity: f32 }, // CRT-style darkening of alternate rows; applied to the lit color
+    Bloom { strength: f32 },      // brightens the brightest parts; applied to the lit color
+    Graph(Arc<MaterialGraph>),    // node graph loaded from a file (color, and coverage if it outputs alpha)
+    Expr(Arc<ExprProgram>),       // shader expressions loaded from a file (`color`, and coverage from `alpha`)
+}
+
+impl LayerKind {
+    /// Screen effects act on the final lit color: lighting happens before the first of them
+    pub fn is_post(&self) -> bool {
+        matches!(self, LayerKind::Scanlines { .. } | LayerKind::Bloom { .. })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LayerKind::Surface => "surface",
+            LayerKind::Bands(_) => "bands",
+            LayerKind::Noise(_) => "noise",
+            LayerKind::Scanlines { .. } => "scanlines",
+            LayerKind::Bloom { .. } => "bloom",
+            LayerKind::Graph(_) => "graph",
+            LayerKind::Expr(_) => "expr",
+        }
+    }
+}
+
+/// One entry of a material's layer stack, composited bottom (index 0) to top
+#[derive(Clone, Debug, PartialEq)]
+pub struct Layer {
+    pub kind: LayerKind,
+    pub blend: LayerBlend,
+    pub opacity: f32,
+    pub enabled: bool,
+}
+
+impl Layer {
+    pub fn new(kind: LayerKind, blend: LayerBlend) -> Self {
+        Layer { kind, blend, opacity: 1.0, enabled: true }
+    }
+    pub fn surface() -> Self {
+        Self::new(LayerKind::Surface, LayerBlend::Normal)
+    }
+    pub fn bands(params: BandsLayer) -> Self {
+        Self::new(LayerKind::Bands(params), LayerBlend::Normal)
+    }
+    pub fn noise(params: NoiseLayer) -> Self {
+        Self::new(LayerKind::Noise(params), LayerBlend::Multiply)
+    }
+    pub fn scanlines(intensity: f32) -> Self {
+        Self::new(LayerKind::Scanlines { intensity }, LayerBlend::Multiply)
+    }
+    pub fn bloom(strength: f32) -> Self {
+        Self::new(LayerKind::Bloom { strength }, LayerBlend::Add)
+    }
+    pub fn graph(graph: MaterialGraph) -> Self {
+        Self::new(LayerKind::Graph(Arc::new(graph)), LayerBlend::Normal)
+    }
+    pub fn expr(program: ExprProgram) -> Self {
+        Self::new(LayerKind::Expr(Arc::new(program)), LayerBlend::Normal)
+    }
+    pub fn with_blend(mut self, blend: LayerBlend) -> Self {
+        self.blend = blend;
+        self
+    }
+    pub fn with_opacity(mut self, opacity: f32) -> Self {
+        self.opacity = opacity;
+        self
+    }
+}
+
+/// Puts `src` over `dst` with `mode`, then fades the result in by `amount` (opacity x coverage)
+pub fn composite(dst: Vector3, src: Vector3, mode: LayerBlend, amount: f32) -> Vector3 {
+    let channel = |d: f32, s: f32| match mode {
+        LayerBlend::Normal => s,
+        LayerBlend::Multiply => d * s,
+        LayerBlend::Screen => 1.0 - (1.0 - d) * (1.0 - s),
+        LayerBlend::Overlay => if d < 0.5 { 2.0 * d * s } else { 1.0 - 2.0 * (1.0 - d) * (1.0 - s) },
+        LayerBlend::Add => d + s,
+    };
+    let out = Vector3::new(channel(dst.x, src.x), channel(dst.y, src.y), channel(dst.z, src.z));
+    let a = amount.clamp(0.0, 1.0);
+    dst * (1.0 - a) + out * a
+}
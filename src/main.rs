@@ -9,6 +9,8 @@ fn rotate_y(v: Vector3, ang: f32) -> Vector3 {
 
 use raylib::prelude::*;
 use std::f32::consts::PI;
+use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Instant;
 
 mod framebuffer;
@@ -17,20 +19,57 @@ mod obj;
 mod matrix;
 mod line;
 mod triangle;
+mod vertex;
 mod fragment;
-mod light;
 mod shaders;
 mod uniforms;
 mod procedural;
+mod blackbody;
+mod sky;
+mod craters;
+mod terrain;
+mod gas_giant;
+mod star;
+mod clouds;
+mod cities;
+mod rings;
+mod texture;
+mod bump;
+mod layers;
+mod graph;
+mod expr;
+mod ramp;
+mod bake;
+mod heightmap;
+mod palette_fit;
+mod orbit;
+mod nbody;
 
 use framebuffer::Framebuffer;
 use camera::Camera;
 use obj::Obj;
-use shaders::Material;
+use shaders::{BlendMode, Material, ShadingMode};
+use sky::Sky;
+use vertex::{Vertex, VertexAttributes};
+use craters::{crater_field, CraterParams};
+use star::CoronaParams;
+use clouds::CloudParams;
+use cities::CityLightParams;
+use blackbody::blackbody_linear;
+use heightmap::Heightmap;
+use bump::NormalMap;
+use ramp::ColorRamp;
+use texture::{TextureMap, UvMapping};
+use terrain::{terrain_height, TerrainParams};
+use orbit::KeplerOrbit;
+use nbody::{Body, Integrator, NBody};
+use graph::MaterialGraph;
+use expr::ExprProgram;
+use layers::Layer;
 
 use triangle::triangle;
-use crate::{light::Light, matrix::{create_model_matrix, create_projection_matrix, create_view_matrix, create_viewport_matrix, multiply_matrix_vector4}, shaders::fragment_shader, uniforms::{Uniforms, vec3_to_color}};
-use crate::procedural::{generate_uv_sphere, generate_ring};
+use crate::{matrix::{create_model_matrix, create_projection_matrix, create_view_matrix, create_viewport_matrix, multiply_matrix_vector4}, shaders::{fragment_alpha, fragment_shader, vertex_lighting}, uniforms::{Uniforms, vec3_to_color}};
+use crate::procedural::{generate_uv_sphere, generate_ring, smooth_normals, uv_tangents, noise3, derive_seed, NoiseBasis};
 
 // --- Scene entities ---
 #[derive(Clone)]
@@ -38,22 +77,25 @@ enum Motion {
     Static,
     Orbit { center: Vector3, radius: f32, angular_speed: f32, phase: f32 }, // world-center orbit
     OrbitAround { parent: &'static str, radius: f32, angular_speed: f32, phase: f32 }, // orbit around entity
+    Kepler { parent: Option<&'static str>, orbit: KeplerOrbit }, // elliptical, inclined; around the origin if no parent
 }
 
 #[derive(Clone)]
 enum VertexShader {
     Identity,
-    DisplaceSpherical { amp: f32, freq: f32, octaves: u32, lacunarity: f32, gain: f32, time_amp: f32 },
-    DisplacePlanarY  { amp: f32, freq: f32, octaves: u32, lacunarity: f32, gain: f32, time_amp: f32 },
+    DisplaceSpherical { amp: f32, freq: f32, octaves: u32, lacunarity: f32, gain: f32, time_amp: f32, basis: NoiseBasis, seed: u32 },
+    DisplacePlanarY  { amp: f32, freq: f32, octaves: u32, lacunarity: f32, gain: f32, time_amp: f32, basis: NoiseBasis, seed: u32 },
+    // Spherical FBM plus a seeded impact-crater field (same field the fragment stage shades)
+    Cratered { amp: f32, freq: f32, octaves: u32, lacunarity: f32, gain: f32, time_amp: f32, basis: NoiseBasis, seed: u32, craters: CraterParams },
+    // Radial offset scale * (elevation - sea_level) from an equirectangular heightmap (elevation 0..1)
+    Heightmap { map: Arc<Heightmap>, scale: f32, sea_level: f32 },
+    // Land raised by amp * (height - sea_level) from the same field `Material::terrain` colors; oceans stay flat
+    Terrain { amp: f32, terrain: TerrainParams, seed: u32 },
 }
 
 #[derive(Clone)]
 struct ShaderConfig {
-    pub enabled: bool,  // master on/off for this entity shader
-    pub layer1: bool,   // e.g., base albedo / palette
-    pub layer2: bool,   // e.g., stripes/rings
-    pub layer3: bool,   // e.g., noise/perturbation
-    pub layer4: bool,   // e.g., scanlines/accents
+    pub enabled: bool,  // master on/off for this entity shader (layers toggle on `material.layers`)
 }
 
 #[derive(Clone, Copy)]
@@ -69,50 +111,189 @@ struct Entity {
     rotation: Vector3,
     scale: f32,
     motion: Motion,
+    mass: f32,                // gravity in the N-body mode (G = 1); 0 = carried along by `motion` there too
     vertices: Vec<Vector3>,
+    uvs: Vec<Vector2>,        // per-vertex UVs (imported meshes); empty for procedural ones
     vshader: VertexShader,
+    baked: DisplacementCache, // filled once the vertex shader is seeded
     spin: Vector3,            // angular velocity (rad/s) around each local axis
     face_tangent: bool,       // if true, add tangent-facing yaw from orbital motion
     shader: ShaderConfig,
     material: Material,
 }
 
-fn apply_vertex_shader(v: Vector3, shader: &VertexShader, time: f32) -> Vector3 {
+impl VertexShader {
+    /// Same shader with its noise seed replaced
+    fn with_seed(self, new_seed: u32) -> Self {
+        match self {
+            VertexShader::Identity => VertexShader::Identity,
+            VertexShader::DisplaceSpherical { seed: _, amp, freq, octaves, lacunarity, gain, time_amp, basis } =>
+                VertexShader::DisplaceSpherical { amp, freq, octaves, lacunarity, gain, time_amp, basis, seed: new_seed },
+            VertexShader::DisplacePlanarY { seed: _, amp, freq, octaves, lacunarity, gain, time_amp, basis } =>
+                VertexShader::DisplacePlanarY { amp, freq, octaves, lacunarity, gain, time_amp, basis, seed: new_seed },
+            VertexShader::Cratered { seed: _, amp, freq, octaves, lacunarity, gain, time_amp, basis, craters } =>
+                VertexShader::Cratered { amp, freq, octaves, lacunarity, gain, time_amp, basis, craters, seed: new_seed },
+            VertexShader::Terrain { amp, terrain, .. } => VertexShader::Terrain { amp, terrain, seed: new_seed },
+            VertexShader::Heightmap { .. } => self,
+        }
+    }
+}
+
+#[inline]
+fn radial(v: Vector3) -> Vector3 {
+    let len = (v.x*v.x + v.y*v.y + v.z*v.z).sqrt().max(1e-6);
+    Vector3::new(v.x/len, v.y/len, v.z/len)
+}
+
+/// Time-independent part of the vertex shader (all octaves); baked once per entity
+fn displace_static(v: Vector3, shader: &VertexShader) -> Vector3 {
     match shader {
         VertexShader::Identity => v,
-        VertexShader::DisplaceSpherical { amp, freq, octaves, lacunarity, gain, time_amp } => {
-            // Normal is radial for spheres
-            let len = (v.x*v.x + v.y*v.y + v.z*v.z).sqrt().max(1e-6);
-            let n = Vector3::new(v.x/len, v.y/len, v.z/len);
-            // Sample FBM in object space around the surface; animate with time
-            let p = Vector3::new(v.x * *freq, v.y * *freq, v.z * *freq) + Vector3::new(0.0, 0.0, time * *time_amp);
-            let h = crate::procedural::fbm3(p, *octaves, *lacunarity, *gain); // ~[-1,1]
-            let disp = *amp * h;
-            Vector3::new(v.x + n.x * disp, v.y + n.y * disp, v.z + n.z * disp)
-        }
-        VertexShader::DisplacePlanarY { amp, freq, octaves, lacunarity, gain, time_amp } => {
+        VertexShader::DisplaceSpherical { amp, freq, octaves, lacunarity, gain, basis, seed, .. } => {
+            // Normal is radial for spheres; sample FBM in object space around the surface
+            let h = noise3(*basis, v * *freq, *octaves, *lacunarity, *gain, *seed); // ~[-1,1]
+            v + radial(v) * (*amp * h)
+        }
+        VertexShader::DisplacePlanarY { amp, freq, octaves, lacunarity, gain, basis, seed, .. } => {
             // For rings/planes, displace along +Y using FBM in XZ
-            let p = Vector3::new(v.x * *freq, 0.0, v.z * *freq) + Vector3::new(0.0, 0.0, time * *time_amp);
-            let h = crate::procedural::fbm3(p, *octaves, *lacunarity, *gain); // ~[-1,1]
-            let disp = *amp * h;
-            Vector3::new(v.x, v.y + disp, v.z)
+            let p = Vector3::new(v.x * *freq, 0.0, v.z * *freq);
+            let h = noise3(*basis, p, *octaves, *lacunarity, *gain, *seed); // ~[-1,1]
+            Vector3::new(v.x, v.y + *amp * h, v.z)
+        }
+        VertexShader::Cratered { amp, freq, octaves, lacunarity, gain, basis, seed, craters, .. } => {
+            let h = noise3(*basis, v * *freq, *octaves, *lacunarity, *gain, *seed);
+            // Crater heights are in unit-sphere units; scale them to this sphere's radius
+            let radius = v.length();
+            let c = crater_field(v, craters, *seed).height * radius;
+            v + radial(v) * (*amp * h + c)
+        }
+        VertexShader::Heightmap { map, scale, sea_level } => v + radial(v) * (*scale * (map.sample(v) - *sea_level)),
+        VertexShader::Terrain { amp, terrain, seed } => {
+            v + radial(v) * (*amp * (terrain_height(v, terrain, *seed) - terrain.sea_level).max(0.0))
+        }
+    }
+}
+
+/// Time-varying part: the base octave drifting along Z at `time_amp`, as a height along
+/// `displacement_dir`. Subtract its t = 0 value to get the offset over the baked mesh.
+fn animated_height(v: Vector3, shader: &VertexShader, time: f32) -> f32 {
+    match shader {
+        VertexShader::Identity | VertexShader::Heightmap { .. } | VertexShader::Terrain { .. } => 0.0,
+        VertexShader::DisplaceSpherical { amp, freq, lacunarity, gain, time_amp, basis, seed, .. }
+        | VertexShader::Cratered { amp, freq, lacunarity, gain, time_amp, basis, seed, .. } => {
+            let p = v * *freq + Vector3::new(0.0, 0.0, time * *time_amp);
+            *amp * noise3(*basis, p, 1, *lacunarity, *gain, *seed)
+        }
+        VertexShader::DisplacePlanarY { amp, freq, lacunarity, gain, time_amp, basis, seed, .. } => {
+            let p = Vector3::new(v.x * *freq, 0.0, v.z * *freq + time * *time_amp);
+            *amp * noise3(*basis, p, 1, *lacunarity, *gain, *seed)
+        }
+    }
+}
+
+/// Height the vertex stage displaces `v` by at `time` (along `displacement_dir`)
+fn mesh_height(v: Vector3, shader: &VertexShader, time: f32) -> f32 {
+    let offset = displace_static(v, shader) - v;
+    offset.dot(displacement_dir(v, shader)) + animated_height(v, shader, time) - animated_height(v, shader, 0.0)
+}
+
+fn displacement_dir(v: Vector3, shader: &VertexShader) -> Vector3 {
+    match shader {
+        VertexShader::Identity => Vector3::zero(),
+        VertexShader::DisplaceSpherical { .. } | VertexShader::Cratered { .. } | VertexShader::Heightmap { .. } | VertexShader::Terrain { .. } => radial(v),
+        VertexShader::DisplacePlanarY { .. } => Vector3::new(0.0, 1.0, 0.0),
+    }
+}
+
+fn is_animated(shader: &VertexShader) -> bool {
+    match shader {
+        VertexShader::Identity | VertexShader::Heightmap { .. } | VertexShader::Terrain { .. } => false,
+        VertexShader::DisplaceSpherical { time_amp, .. }
+        | VertexShader::DisplacePlanarY { time_amp, .. }
+        | VertexShader::Cratered { time_amp, .. } => *time_amp != 0.0,
+    }
+}
+
+/// Vertex stage output cached per entity. The static displacement (every octave) and its
+/// normals are baked once; bodies with `time_amp != 0` then only add their drifting base
+/// octave each frame, once per distinct vertex, and re-sum the smooth normals in place.
+/// Tangents stay baked: the drift bends the surface but barely turns its UV frame.
+#[derive(Clone, Default)]
+struct DisplacementCache {
+    positions: Vec<Vector3>,   // displaced object-space positions for the current frame
+    normals: Vec<Vector3>,     // smooth normals of `positions`
+    tangents: Vec<Vector3>,    // UV tangent frame per vertex, from the t = 0 mesh; empty without UVs
+    bitangents: Vec<Vector3>,
+    slot: Vec<u32>,            // distinct vertex of each triangle-list vertex; empty when fully static
+    distinct: Vec<Vector3>,    // undisplaced distinct vertices
+    rest: Vec<Vector3>,        // their static displacement
+    rest_height: Vec<f32>,     // their `animated_height` at t = 0
+    moved: Vec<Vector3>,       // scratch: this frame's position of each distinct vertex
+    summed: Vec<Vector3>,      // scratch: area-weighted face normals around each distinct vertex
+}
+
+impl DisplacementCache {
+    fn bake(vertices: &[Vector3], uvs: &[Vector2], shader: &VertexShader) -> Self {
+        let positions: Vec<Vector3> = vertices.iter().map(|v| displace_static(*v, shader)).collect();
+        let normals = smooth_normals(&positions);
+        let (tangents, bitangents) = uv_tangents(&positions, uvs);
+        let mut cache = DisplacementCache { positions, normals, tangents, bitangents, ..Default::default() };
+        if is_animated(shader) {
+            // Same welding as `smooth_normals`, so seam copies share one normal while animating
+            let key = |p: Vector3| ((p.x * 1e4).round() as i32, (p.y * 1e4).round() as i32, (p.z * 1e4).round() as i32);
+            let mut seen: HashMap<(i32, i32, i32), u32> = HashMap::new();
+            for (v, baked) in vertices.iter().zip(&cache.positions) {
+                let next = cache.distinct.len() as u32;
+                let s = *seen.entry(key(*v)).or_insert(next);
+                if s == next {
+                    cache.distinct.push(*v);
+                    cache.rest.push(*baked);
+                    cache.rest_height.push(animated_height(*v, shader, 0.0));
+                }
+                cache.slot.push(s);
+            }
+            cache.moved = cache.rest.clone();
+            cache.summed = vec![Vector3::zero(); cache.distinct.len()];
+        }
+        cache
+    }
+
+    /// Moves `positions` and `normals` to `time`, reusing the cache's buffers; no-op when static
+    fn animate(&mut self, shader: &VertexShader, time: f32) {
+        if self.slot.is_empty() {
+            return;
+        }
+        for (((m, v), rest), h0) in self.moved.iter_mut().zip(&self.distinct).zip(&self.rest).zip(&self.rest_height) {
+            *m = *rest + displacement_dir(*v, shader) * (animated_height(*v, shader, time) - h0);
+        }
+        for (p, &s) in self.positions.iter_mut().zip(&self.slot) {
+            *p = self.moved[s as usize];
+        }
+        // Area-weighted face normals summed per distinct vertex, as `smooth_normals` does
+        self.summed.iter_mut().for_each(|n| *n = Vector3::zero());
+        for (tri, s) in self.positions.chunks_exact(3).zip(self.slot.chunks_exact(3)) {
+            let n = (tri[1] - tri[0]).cross(tri[2] - tri[0]);
+            for &k in s {
+                self.summed[k as usize] += n;
+            }
+        }
+        for (n, &s) in self.normals.iter_mut().zip(&self.slot) {
+            let sum = self.summed[s as usize];
+            *n = if sum.length() > 1e-12 { sum.normalized() } else { Vector3::new(0.0, 1.0, 0.0) };
         }
     }
 }
 
 fn transform(
     vertex: Vector3,
-    translation: Vector3,
-    scale: f32,
-    rotation: Vector3,
+    model: &Matrix,
     view: &Matrix,
     projection: &Matrix,
     viewport: &Matrix,
-) -> Vector3 {
-    let model : Matrix = create_model_matrix(translation, scale, rotation);
+) -> (Vector3, Vector3) {
     let vertex4 = Vector4::new(vertex.x, vertex.y, vertex.z, 1.0);
 
-    let world_transform = multiply_matrix_vector4(&model, &vertex4);
+    let world_transform = multiply_matrix_vector4(model, &vertex4);
     let view_transform = multiply_matrix_vector4(view, &world_transform);
     let projection_transform = multiply_matrix_vector4(projection, &view_transform);
 
@@ -126,7 +307,8 @@ fn transform(
 
     // Viewport una sola vez (x,y), pero mantenemos depth en NDC [-1,1] para el Z-buffer
     let screen = multiply_matrix_vector4(viewport, &ndc);
-    Vector3::new(screen.x, screen.y, ndc.z)
+    let world = Vector3::new(world_transform.x, world_transform.y, world_transform.z);
+    (Vector3::new(screen.x, screen.y, ndc.z), world)
 }
 
 pub fn render(
@@ -135,22 +317,44 @@ pub fn render(
     scale: f32,
     rotation: Vector3,
     vertex_array: &[Vector3],
+    attributes: &VertexAttributes,
     view: &Matrix,
     projection: &Matrix,
     viewport: &Matrix,
-    time: f32,
-    resolution: Vector2,
-    vshader: &VertexShader,
+    uniforms: &Uniforms,
     apply_shader: bool,
-    layers: (bool, bool, bool, bool),
     material: &Material,
 ) {
-    let light = Light::new(Vector3::new(0.0, 10.0, 0.0));
+    let model = create_model_matrix(translation, scale, rotation);
+
+    // Direction to the star in this entity's object space (transpose of the rotation)
+    let mut uniforms = *uniforms;
+    uniforms.model = model;
+    let to_light = uniforms.light_pos - translation;
+    uniforms.light_dir_obj = Vector3::new(
+        model.m0 * to_light.x + model.m1 * to_light.y + model.m2 * to_light.z,
+        model.m4 * to_light.x + model.m5 * to_light.y + model.m6 * to_light.z,
+        model.m8 * to_light.x + model.m9 * to_light.y + model.m10 * to_light.z,
+    ).normalized();
+
+    // Normals only see the rotation (uniform scale, w = 0 drops the translation)
+    let to_world = |v: Vector3| {
+        let v4 = multiply_matrix_vector4(&model, &Vector4::new(v.x, v.y, v.z, 0.0));
+        Vector3::new(v4.x, v4.y, v4.z).normalized()
+    };
     let mut transformed_vertices = Vec::with_capacity(vertex_array.len());
-    for vertex in vertex_array {
-        let v_obj = apply_vertex_shader(*vertex, vshader, time);
-        let transformed = transform(v_obj, translation, scale, rotation, view, projection, viewport);
-        transformed_vertices.push(transformed);
+    for (i, vertex) in vertex_array.iter().enumerate() {
+        let (screen, world_pos) = transform(*vertex, &model, view, projection, viewport);
+        let normal = to_world(attributes.normals.get(i).copied().unwrap_or_else(|| vertex.normalized()));
+        let uv = attributes.uvs.get(i).copied().unwrap_or(Vector2::zero());
+        let tangent = attributes.tangents.get(i).map_or(Vector3::zero(), |t| to_world(*t));
+        let bitangent = attributes.bitangents.get(i).map_or(Vector3::zero(), |b| to_world(*b));
+        let (light_diffuse, light_specular) = if apply_shader && material.shading == ShadingMode::Gouraud {
+            vertex_lighting(*vertex, world_pos, normal, &uniforms, material)
+        } else {
+            (Vector3::zero(), Vector3::zero())
+        };
+        transformed_vertices.push(Vertex { screen, obj_pos: *vertex, world_pos, normal, uv, tangent, bitangent, light_diffuse, light_specular });
     }
 
     // Primitive Assembly Stage
@@ -168,58 +372,275 @@ pub fn render(
     // Rasterization Stage
     let mut fragments = Vec::new();
     for tri in &triangles {
-        fragments.extend(triangle(&tri[0], &tri[1], &tri[2], &light));
+        fragments.extend(triangle(&tri[0], &tri[1], &tri[2], material.shading));
+    }
+
+    // Transparent shells have nothing sensible to show without their shader
+    if material.blend != BlendMode::Opaque && !apply_shader {
+        return;
     }
-    
-    let uniforms = Uniforms {
-        time,
-        resolution,
-    };
 
     // Fragment Processing Stage
     for fragment in fragments {
         let final_rgb = if apply_shader {
-            // For now, we pass through to the shared shader; later we will expand to use layers tuple.
-            fragment_shader(&fragment, &uniforms, layers, material)
+            fragment_shader(&fragment, &uniforms, material)
         } else {
-            // Fallback: use fragment base color as 0..1
-            Vector3::new(
-                fragment.color.x.min(255.0) / 255.0,
-                fragment.color.y.min(255.0) / 255.0,
-                fragment.color.z.min(255.0) / 255.0,
-            )
+            // Fallback: the rasterizer's flat color, a plain silhouette
+            fragment.color
         };
         let out = vec3_to_color(final_rgb);
-        framebuffer.set_current_color(out);
-        framebuffer.set_pixel(
-            fragment.position.x as u32,
-            fragment.position.y as u32,
-            fragment.depth
+        let (x, y) = (fragment.position.x as u32, fragment.position.y as u32);
+        match material.blend {
+            BlendMode::Opaque => {
+                framebuffer.set_current_color(out);
+                framebuffer.set_pixel(x, y, fragment.depth);
+            }
+            BlendMode::Alpha => {
+                let alpha = fragment_alpha(&fragment, &uniforms, material);
+                framebuffer.blend_pixel(x, y, fragment.depth, out, alpha);
+            }
+            BlendMode::Additive => framebuffer.add_pixel(x, y, fragment.depth, out),
+        }
+    }
+
+}
+
+/// World position of every entity from its `Motion` at `time`: world-centered orbits first, then
+/// children around their parents. Entities with a `pinned` position (bodies of the N-body
+/// simulation) take it as-is, and their children follow them.
+fn motion_positions(entities: &[Entity], time: f32, pinned: &[Option<Vector3>]) -> Vec<Vector3> {
+    let index_by_name: HashMap<&'static str, usize> = entities.iter().enumerate().map(|(i,e)| (e.name, i)).collect();
+    let mut positions: Vec<Vector3> = entities.iter().map(|e| e.translation).collect();
+
+    // Pass 1: update world-centered orbits and statics
+    for (i, e) in entities.iter().enumerate() {
+        if let Some(p) = pinned.get(i).copied().flatten() {
+            positions[i] = p;
+            continue;
+        }
+        match e.motion {
+            Motion::Static => { /* no-op */ }
+            Motion::Orbit { center, radius, angular_speed, phase } => {
+                let theta = phase + angular_speed * time;
+                positions[i] = Vector3::new(center.x + radius * theta.cos(), center.y, center.z + radius * theta.sin());
+            }
+            Motion::Kepler { parent: None, orbit } => {
+                positions[i] = orbit.position(time);
+            }
+            Motion::OrbitAround { .. } | Motion::Kepler { .. } => { /* defer to pass 2 */ }
+        }
+    }
+
+    // Pass 2: update children that orbit around a parent (world-axes offset around parent's position)
+    for (i, e) in entities.iter().enumerate() {
+        if matches!(pinned.get(i), Some(Some(_))) {
+            continue;
+        }
+        match e.motion {
+            Motion::OrbitAround { parent, radius, angular_speed, phase } => {
+                if let Some(&pi) = index_by_name.get(parent) {
+                    let theta = phase + angular_speed * time;
+                    // radius 0 keeps it centered on the parent (clouds, rings)
+                    positions[i] = positions[pi] + Vector3::new(radius * theta.cos(), 0.0, radius * theta.sin());
+                }
+            }
+            Motion::Kepler { parent: Some(parent), orbit } => {
+                if let Some(&pi) = index_by_name.get(parent) {
+                    positions[i] = positions[pi] + orbit.position(time);
+                }
+            }
+            _ => {}
+        }
+    }
+    positions
+}
+
+/// Entity `i` goes around: its named parent, or the heaviest body for world-centered orbits
+/// (None for that body itself)
+fn orbit_parent(entities: &[Entity], i: usize) -> Option<usize> {
+    let named = |name: &str| entities.iter().position(|e| e.name == name);
+    match entities[i].motion {
+        Motion::Static => None,
+        Motion::OrbitAround { parent, .. } | Motion::Kepler { parent: Some(parent), .. } => named(parent),
+        Motion::Orbit { .. } | Motion::Kepler { parent: None, .. } => (0..entities.len())
+            .max_by(|&a, &b| entities[a].mass.total_cmp(&entities[b].mass))
+            .filter(|&j| j != i),
+    }
+}
+
+const NBODY_DT: f32 = 1.0 / 240.0;
+const NBODY_MAX_FRAME: f64 = 0.25; // simulated seconds per frame at most, so a slow frame can't snowball
+
+/// The scene's massive entities integrated under their mutual gravity
+struct NBodyScene {
+    sim: NBody,
+    bodies: Vec<usize>, // entity index of each simulated body
+}
+
+impl NBodyScene {
+    /// Starts from where the analytic motions put everything at `time`. Each body gets the
+    /// vis-viva speed for its orbit around its parent (circular for `Orbit`/`OrbitAround`),
+    /// along the direction its `Motion` was moving, and the whole system is shifted to the
+    /// barycentre's rest frame so it doesn't drift off.
+    ///
+    /// Those speeds come from the masses (sun 1000, G = 1), while the analytic angular speeds
+    /// were picked by eye and aren't Keplerian for any single sun mass (ω²a³ runs from about
+    /// 640 to 53000), so periods change when the simulation takes over: planet_rocky3 at
+    /// a = 50 slows down about 7×.
+    fn start(entities: &[Entity], time: f32, integrator: Integrator, dt: f32) -> Self {
+        const PROBE: f32 = 1e-3; // finite-difference step for the direction of motion
+        let before = motion_positions(entities, time, &[]);
+        let after = motion_positions(entities, time + PROBE, &[]);
+
+        fn velocity(entities: &[Entity], i: usize, before: &[Vector3], after: &[Vector3]) -> Vector3 {
+            let Some(pi) = orbit_parent(entities, i) else { return Vector3::zero() };
+            let offset = before[i] - before[pi];
+            let r = offset.length();
+            let a = match entities[i].motion {
+                Motion::Orbit { radius, .. } | Motion::OrbitAround { radius, .. } => radius,
+                Motion::Kepler { orbit, .. } => orbit.semi_major_axis,
+                Motion::Static => 0.0,
+            };
+            let gm = entities[pi].mass + entities[i].mass;
+            let direction = (after[i] - after[pi]) - offset;
+            let speed = if r > 0.0 && a > 0.0 && direction.length() > 0.0 { (gm * (2.0 / r - 1.0 / a)).max(0.0).sqrt() } else { 0.0 };
+            let relative = if speed > 0.0 { direction.normalized() * speed } else { Vector3::zero() };
+            relative + velocity(entities, pi, before, after)
+        }
+
+        let indices: Vec<usize> = (0..entities.len()).filter(|&i| entities[i].mass > 0.0).collect();
+        let mut bodies: Vec<Body> = indices.iter()
+            .map(|&i| Body { position: before[i], velocity: velocity(entities, i, &before, &after), mass: entities[i].mass })
+            .collect();
+        let total_mass: f32 = bodies.iter().map(|b| b.mass).sum();
+        let drift = bodies.iter().fold(Vector3::zero(), |p, b| p + b.velocity * b.mass) / total_mass.max(1e-6);
+        for b in bodies.iter_mut() {
+            b.velocity -= drift;
+        }
+        NBodyScene { sim: NBody::new(&bodies, integrator, dt), bodies: indices }
+    }
+
+    /// Simulated positions in entity order (None for entities left to their `Motion`)
+    fn pinned(&self, entity_count: usize) -> Vec<Option<Vector3>> {
+        let mut pinned = vec![None; entity_count];
+        for (k, &i) in self.bodies.iter().enumerate() {
+            pinned[i] = Some(self.sim.position(k));
+        }
+        pinned
+    }
+}
+
+/// `nbody [seconds] [dt] [integrator]`: runs the scene's bodies from the same start with each
+/// integrator (or just the one named) and prints how far energy and momentum drift
+fn nbody_report(entities: &[Entity], args: &[String]) -> Result<(), String> {
+    let usage = "usage: nbody [seconds=60] [dt=1/240] [leapfrog|rk4|rk45]";
+    let seconds: f64 = args.first().map_or(Ok(60.0), |a| a.parse()).map_err(|_| usage)?;
+    let dt: f32 = args.get(1).map_or(Ok(NBODY_DT), |a| a.parse()).map_err(|_| usage)?;
+    let integrators = match args.get(2) {
+        Some(name) => vec![Integrator::named(name).ok_or(usage)?],
+        None => Integrator::ALL.to_vec(),
+    };
+    if dt <= 0.0 {
+        return Err(usage.into());
+    }
+
+    let steps = (seconds / dt as f64).round() as u64;
+    let sample_every = ((1.0 / dt as f64).round() as u64).max(1);
+    println!("{} bodies, {} steps of {} s", entities.iter().filter(|e| e.mass > 0.0).count(), steps, dt);
+    println!("{:<10} {:>10} {:>14} {:>12} {:>12} {:>12} {:>9}", "integrator", "substeps", "energy", "max dE/E", "final dE/E", "dP", "ms");
+    for integrator in integrators {
+        let mut scene = NBodyScene::start(entities, 0.0, integrator, dt);
+        let clock = Instant::now();
+        // Sampled once per simulated second: leapfrog's error oscillates, so the worst sample matters
+        let mut max_energy: f64 = 0.0;
+        for s in 1..=steps {
+            scene.sim.step();
+            if s % sample_every == 0 {
+                max_energy = max_energy.max(scene.sim.drift().relative_energy);
+            }
+        }
+        let drift = scene.sim.drift();
+        println!(
+            "{:<10} {:>10} {:>14.6} {:>12.3e} {:>12.3e} {:>12.3e} {:>9.1}",
+            integrator.name(), scene.sim.substeps, drift.energy, max_energy.max(drift.relative_energy),
+            drift.relative_energy, drift.momentum, clock.elapsed().as_secs_f64() * 1000.0,
         );
     }
+    Ok(())
+}
 
+/// Bakes one spherical entity to `<prefix>_albedo/height/normal.png` (equirectangular, 2:1)
+fn bake_entity(entities: &[Entity], args: &[String]) -> Result<(), String> {
+    let name = args.first().ok_or("usage: bake <entity> [width=1024] [time=0] [prefix=<entity>]")?;
+    let e = entities.iter().find(|e| e.name == name.as_str()).ok_or(format!("no entity named {}", name))?;
+    if e.material.rings.is_some() {
+        return Err(format!("{} is a ring system; only spherical bodies can be baked", name));
+    }
+    let width: u32 = args.get(1).map_or(Ok(1024), |a| a.parse()).map_err(|_| "width must be an integer")?;
+    let time: f32 = args.get(2).map_or(Ok(0.0), |a| a.parse()).map_err(|_| "time must be a number")?;
+    let prefix = args.get(3).cloned().unwrap_or_else(|| name.clone());
+
+    let radius = e.vertices.iter().map(|v| v.length()).fold(0.0, f32::max);
+    let maps = bake::bake_sphere(&e.material, radius, width.max(2), (width / 2).max(1), time, |p| mesh_height(p, &e.vshader, time));
+    let (lo, hi) = maps.save(&prefix)?;
+    println!("{}: {}x{} maps written to {}_*.png (height range {:.4}..{:.4})", name, maps.width, maps.height, prefix, lo, hi);
+    Ok(())
+}
+
+/// `fit-palette <image> [name] [prefix]`: fits a cosine palette to the image's colors, prints a
+/// `Material` preset and writes it to `<prefix>_preset.rs` with a `<prefix>_palette.png` comparison strip
+fn fit_palette(args: &[String]) -> Result<(), String> {
+    let path = args.first().ok_or("usage: fit-palette <image> [name=fitted] [prefix=<name>]")?;
+    let name = args.get(1).cloned().unwrap_or_else(|| "fitted".to_string());
+    let prefix = args.get(2).cloned().unwrap_or_else(|| name.clone());
+
+    let ident = palette_fit::preset_ident(&name)?;
+    let fit = palette_fit::fit_image(path)?;
+    let preset = fit.preset(&ident, path);
+    print!("{}", preset);
+    std::fs::write(format!("{}_preset.rs", prefix), &preset).map_err(|e| format!("{}_preset.rs: {}", prefix, e))?;
+    fit.save_preview(&format!("{}_palette.png", prefix), 512, 64)?;
+    eprintln!("rms error {:.4}; wrote {}_preset.rs and {}_palette.png (top: source, bottom: fit)", fit.rms, prefix, prefix);
+    Ok(())
 }
 
 fn main() {
+    // `wireframe fit-palette <image> [name] [prefix]`: needs no scene, so it runs before anything is built
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("fit-palette") {
+        if let Err(e) = fit_palette(&args[2..]) {
+            eprintln!("fit-palette: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let window_width = 1300;
     let window_height = 600;
 
-    let (mut window, raylib_thread) = raylib::init()
-        .size(window_width, window_height)
-        .title("Wireframe")
-        .log_level(TraceLogLevel::LOG_WARNING)
-        .build();
-
-    let projection = create_projection_matrix(PI/3.0, window_width as f32 / window_height as f32, 0.5, 100.0);
+    let fov_y = PI/3.0;
+    let projection = create_projection_matrix(fov_y, window_width as f32 / window_height as f32, 0.5, 100.0);
     let viewport = create_viewport_matrix(0.0, 0.0, window_width as f32, window_height as f32);
 
     let mut framebuffer = Framebuffer::new(window_width as u32, window_height as u32, Color::BLACK);
-    framebuffer.set_background_color(Color::new(4, 12, 36, 255));
+
+    // Global scene seed: every procedural body and the sky derive their noise from it
+    let scene_seed: u32 = 1337;
+
+    // Effective temperature of the central star (K): sets its color and the light on the planets.
+    // Try 3200.0 for a red dwarf or 20000.0 for a blue-white star.
+    let sun_temperature: f32 = 5772.0;
+
+    // Procedural starfield + nebula drawn behind the scene
+    let sky = Sky::new(scene_seed);
+    // The sky covers every empty pixel each frame; clear to its base color so the two agree
+    framebuffer.set_background_color(vec3_to_color(sky.base));
 
     // --- Load / build meshes ---
-    // Ship from OBJ (as before)
+    // Ship from OBJ (as before), with its UVs for the hull's normal map
     let ship_obj = Obj::load("nave.obj").unwrap_or_else(|_| Obj::load("sphere.obj").expect("Failed to load any mesh"));
     let ship_vertices = ship_obj.get_vertex_array();
+    let ship_uvs = ship_obj.get_uv_array();
 
     // Procedural planet (UV-sphere)
     let planet_vertices = generate_uv_sphere(1.2, 24, 32);
@@ -227,24 +648,92 @@ fn main() {
     // Procedural ring (annulus). Tip: tilt by rotating the entity (rotation.x)
     let ring_vertices = generate_ring(1.6, 2.4, 128);
 
+    // Clouds of planet_rocky: the shell and the planet's ground shadows share these params (and seed)
+    let earth_clouds = CloudParams { seed: derive_seed(scene_seed, "planet_rocky_clouds"), ..CloudParams::earth() };
+
     // Procedural moon (smaller UV-sphere)
-    let moon_vertices = generate_uv_sphere(0.4, 16, 24);
+    let moon_vertices = generate_uv_sphere(0.4, 32, 48);
+
+    // Real lunar topography when moon_dem.pgm is in the working directory (equirectangular,
+    // 8-bit image or 16-bit binary PGM); procedural craters otherwise. The file is optional,
+    // so only a DEM that exists but doesn't load is worth a warning.
+    let moon_dem = if std::path::Path::new("moon_dem.pgm").exists() {
+        Heightmap::load("moon_dem.pgm").map_err(|e| eprintln!("{}", e)).ok()
+    } else {
+        None
+    };
+    let (moon_vshader, moon_material) = match moon_dem {
+        Some(dem) => (
+            VertexShader::Heightmap { map: Arc::new(dem), scale: 0.03, sea_level: 0.5 },
+            Material { craters: None, bump: None, ..Material::moon() },
+        ),
+        None => (
+            VertexShader::Cratered { amp: 0.012, freq: 3.0, octaves: 3, lacunarity: 2.0, gain: 0.5, time_amp: 0.0, basis: NoiseBasis::Perlin, seed: 0, craters: CraterParams::lunar() },
+            Material::moon(),
+        ),
+    };
+
+    // Hull panels from a tangent-space normal map (paths are relative to the working directory)
+    let ship_normals = match NormalMap::load("nave_normal.png", 1.0) {
+        Ok(map) => Some(map),
+        Err(e) => {
+            eprintln!("{}", e);
+            None
+        }
+    };
+    let ship_material = Material {
+        shading: ShadingMode::Flat, // low-poly hull: keep its facets
+        craters: None, bump: None, normal_map: ship_normals,
+        roughness: 0.45, metallic: 0.6,
+        ..Material::moon()
+    };
+
+    // Rusty desert for planet_rocky2 from an equirectangular image over its palettes; the inner
+    // palette is the stop-based terrain ramp instead of a cosine one
+    let rocky2_base = Material { pal1_ramp: Some(ColorRamp::terrain()), ..Material::rocky() };
+    let rocky2_material = match TextureMap::load("textures/rocky2_albedo.png", UvMapping::Equirect, 0.7) {
+        Ok(map) => Material { albedo_map: Some(map), ..rocky2_base },
+        Err(e) => {
+            eprintln!("{}", e);
+            rocky2_base
+        }
+    };
+
+    // Artist-authored look for planet_rocky3 from a node graph file (see src/graph.rs)
+    let rocky3_material = match MaterialGraph::load("graphs/lava.graph") {
+        Ok(graph) => Material { layers: vec![Layer::graph(graph), Layer::scanlines(0.04), Layer::bloom(0.1)], ..Material::rocky() },
+        Err(e) => {
+            eprintln!("{}", e);
+            Material::rocky()
+        }
+    };
+
+    // Ice giant look for planet_gas2 written as shader expressions (see src/expr.rs)
+    let gas2_material = match ExprProgram::load("shaders/ice_giant.expr") {
+        Ok(program) => Material { layers: vec![Layer::expr(program), Layer::scanlines(0.05), Layer::bloom(0.12)], ..Material::gaseous() },
+        Err(e) => {
+            eprintln!("{}", e);
+            Material::gaseous()
+        }
+    };
 
     // --- Scene entities ---
     let mut entities: Vec<Entity> = vec![
-        // The ship we will follow
         Entity {
             name: "sun",
             translation: Vector3::new(0.0, 0.0, 0.0),
             rotation: Vector3::new(0.0, 0.0, 0.0),
             scale: 1.0,
             motion: Motion::Static,
+            mass: 1000.0, // planets are 1e-4..1e-3 of this, like the solar system's
             vertices: generate_uv_sphere(3.0, 24, 32),
+            uvs: Vec::new(),
             vshader: VertexShader::Identity,
+            baked: DisplacementCache::default(),
             spin: Vector3::new(0.0, 0.2, 0.0),
             face_tangent: false,
-            shader: ShaderConfig { enabled: true, layer1: true, layer2: true, layer3: true, layer4: true },
-            material: Material::star(),
+            shader: ShaderConfig { enabled: true },
+            material: Material::star_at(sun_temperature),
         },
         
         Entity {
@@ -255,11 +744,14 @@ fn main() {
             motion: Motion::Orbit { 
                 center: Vector3::new(0.0, 0.0, 0.0), radius: 20.0, angular_speed: 0.8, phase: 0.0 
             },
+            mass: 1.0,
             vertices: planet_vertices.clone(),
-            vshader: VertexShader::DisplaceSpherical { amp: 0.08, freq: 2.5, octaves: 4, lacunarity: 2.0, gain: 0.5, time_amp: 0.2 },
+            uvs: Vec::new(),
+            vshader: VertexShader::DisplaceSpherical { amp: 0.08, freq: 2.5, octaves: 4, lacunarity: 2.0, gain: 0.5, time_amp: 0.2, basis: NoiseBasis::Perlin, seed: 0 },
+            baked: DisplacementCache::default(),
             spin: Vector3::new(0.0, 0.6, 0.0),
             face_tangent: false,
-            shader: ShaderConfig { enabled: true, layer1: true, layer2: true, layer3: true, layer4: true },
+            shader: ShaderConfig { enabled: true },
             material: Material::gaseous(),
         },
         Entity {
@@ -270,12 +762,15 @@ fn main() {
             motion: Motion::Orbit { 
                 center: Vector3::new(0.0, 0.0, 0.0), radius: 10.0, angular_speed: 0.8, phase: 0.0 
             },
+            mass: 0.1,
             vertices: generate_uv_sphere(0.8, 16, 24),
-            vshader: VertexShader::DisplaceSpherical { amp: 0.08, freq: 2.5, octaves: 4, lacunarity: 2.0, gain: 0.5, time_amp: 0.2 },
+            uvs: Vec::new(),
+            vshader: VertexShader::Terrain { amp: 0.08, terrain: TerrainParams::earth(), seed: 0 }, // Material::terrain()'s field
+            baked: DisplacementCache::default(),
             spin: Vector3::new(0.0, 1.2, 0.0),
             face_tangent: false,
-            shader: ShaderConfig { enabled: true, layer1: true, layer2: true, layer3: true, layer4: true },
-            material: Material::rocky(),
+            shader: ShaderConfig { enabled: true },
+            material: Material { cloud_shadow: Some(earth_clouds), city_lights: Some(CityLightParams::earth()), ..Material::terrain() },
         },
         // Planet ring (tilt a bit for a nice look)
         Entity {
@@ -289,11 +784,14 @@ fn main() {
                 angular_speed: 0.0,
                 phase: 0.0,
             },
+            mass: 0.0,
             vertices: ring_vertices,
-            vshader: VertexShader::DisplacePlanarY { amp: 0.06, freq: 6.0, octaves: 3, lacunarity: 2.0, gain: 0.55, time_amp: 0.6 },
+            uvs: Vec::new(),
+            vshader: VertexShader::DisplacePlanarY { amp: 0.06, freq: 6.0, octaves: 3, lacunarity: 2.0, gain: 0.55, time_amp: 0.6, basis: NoiseBasis::Value, seed: 0 },
+            baked: DisplacementCache::default(),
             spin: Vector3::new(0.0, 0.0, 0.0),
             face_tangent: false,
-            shader: ShaderConfig { enabled: true, layer1: true, layer2: true, layer3: true, layer4: true },
+            shader: ShaderConfig { enabled: true },
             material: Material::ring(),
         },
         // Moon orbiting the planet procedurally (no external model)
@@ -308,12 +806,15 @@ fn main() {
                 angular_speed: 1.0,
                 phase: 0.0,
             },
+            mass: 0.0, // far outside planet_rocky's Hill sphere at this scale: stays on its analytic orbit
             vertices: moon_vertices,
-            vshader: VertexShader::DisplaceSpherical { amp: 0.03, freq: 3.0, octaves: 3, lacunarity: 2.0, gain: 0.5, time_amp: 0.15 },
+            uvs: Vec::new(),
+            vshader: moon_vshader,
+            baked: DisplacementCache::default(),
             spin: Vector3::new(0.0, 0.8, 0.0),
             face_tangent: true,
-            shader: ShaderConfig { enabled: true, layer1: true, layer2: true, layer3: true, layer4: true },
-            material: Material::rocky(),
+            shader: ShaderConfig { enabled: true },
+            material: moon_material,
         },
         Entity {
             name: "planet_gas2",
@@ -323,12 +824,15 @@ fn main() {
             motion: Motion::Orbit { 
                 center: Vector3::new(0.0, 0.0, 0.0), radius: 40.0, angular_speed: 0.7, phase: 0.0 
             },
+            mass: 0.8,
             vertices: planet_vertices.clone(),
-            vshader: VertexShader::DisplaceSpherical { amp: 0.08, freq: 2.5, octaves: 4, lacunarity: 2.0, gain: 0.5, time_amp: 0.2 },
+            uvs: Vec::new(),
+            vshader: VertexShader::DisplaceSpherical { amp: 0.08, freq: 2.5, octaves: 4, lacunarity: 2.0, gain: 0.5, time_amp: 0.2, basis: NoiseBasis::Perlin, seed: 0 },
+            baked: DisplacementCache::default(),
             spin: Vector3::new(0.0, 0.6, 0.0),
             face_tangent: false,
-            shader: ShaderConfig { enabled: true, layer1: true, layer2: true, layer3: true, layer4: true },
-            material: Material::gaseous(),
+            shader: ShaderConfig { enabled: true },
+            material: gas2_material,
         },
         Entity {
             name: "planet_rocky2",
@@ -338,12 +842,15 @@ fn main() {
             motion: Motion::Orbit { 
                 center: Vector3::new(0.0, 0.0, 0.0), radius: 30.0, angular_speed: 0.75, phase: 0.0 
             },
+            mass: 0.1,
             vertices: generate_uv_sphere(0.8, 16, 24),
-            vshader: VertexShader::DisplaceSpherical { amp: 0.08, freq: 2.5, octaves: 4, lacunarity: 2.0, gain: 0.5, time_amp: 0.2 },
+            uvs: Vec::new(),
+            vshader: VertexShader::DisplaceSpherical { amp: 0.08, freq: 2.5, octaves: 4, lacunarity: 2.0, gain: 0.5, time_amp: 0.2, basis: NoiseBasis::Ridged, seed: 0 },
+            baked: DisplacementCache::default(),
             spin: Vector3::new(0.0, 1.2, 0.0),
             face_tangent: false,
-            shader: ShaderConfig { enabled: true, layer1: true, layer2: true, layer3: true, layer4: true },
-            material: Material::rocky(),
+            shader: ShaderConfig { enabled: true },
+            material: rocky2_material,
         },
         // Planet ring (tilt a bit for a nice look)
         Entity {
@@ -357,11 +864,14 @@ fn main() {
                 angular_speed: 0.0,
                 phase: 0.0,
             },
+            mass: 0.0,
             vertices: generate_ring(1.6, 2.4, 128),
-            vshader: VertexShader::DisplacePlanarY { amp: 0.06, freq: 6.0, octaves: 3, lacunarity: 2.0, gain: 0.55, time_amp: 0.6 },
+            uvs: Vec::new(),
+            vshader: VertexShader::DisplacePlanarY { amp: 0.06, freq: 6.0, octaves: 3, lacunarity: 2.0, gain: 0.55, time_amp: 0.6, basis: NoiseBasis::Value, seed: 0 },
+            baked: DisplacementCache::default(),
             spin: Vector3::new(0.0, 0.0, 0.0),
             face_tangent: false,
-            shader: ShaderConfig { enabled: true, layer1: true, layer2: true, layer3: true, layer4: true },
+            shader: ShaderConfig { enabled: true },
             material: Material::ring(),
         },
         // Moon orbiting the planet procedurally (no external model)
@@ -370,38 +880,157 @@ fn main() {
             translation: Vector3::new(0.0, 0.0, 0.0),
             rotation: Vector3::new(0.0, 0.0, 0.0),
             scale: 1.0,
-            motion: Motion::OrbitAround {
-                parent: "planet_rocky2",
-                radius: 1.5,
-                angular_speed: 1.0,
-                phase: 0.0,
+            motion: Motion::Kepler {
+                parent: Some("planet_rocky2"),
+                orbit: KeplerOrbit {
+                    semi_major_axis: 1.5, eccentricity: 0.1, inclination: 0.45,
+                    ascending_node: 0.0, arg_periapsis: 0.0, mean_anomaly_epoch: 0.0,
+                    mean_motion: 1.0,
+                },
             },
-            vertices: generate_uv_sphere(0.4, 16, 24),
-            vshader: VertexShader::DisplaceSpherical { amp: 0.03, freq: 3.0, octaves: 3, lacunarity: 2.0, gain: 0.5, time_amp: 0.15 },
+            mass: 0.0, // analytic around the (possibly simulated) planet, like `moon`
+            vertices: generate_uv_sphere(0.4, 32, 48),
+            uvs: Vec::new(),
+            vshader: VertexShader::Cratered { amp: 0.012, freq: 3.0, octaves: 3, lacunarity: 2.0, gain: 0.5, time_amp: 0.0, basis: NoiseBasis::Perlin, seed: 0, craters: CraterParams::lunar() },
+            baked: DisplacementCache::default(),
             spin: Vector3::new(0.0, 0.8, 0.0),
             face_tangent: true,
-            shader: ShaderConfig { enabled: true, layer1: true, layer2: true, layer3: true, layer4: true },
-            material: Material::rocky(),
+            shader: ShaderConfig { enabled: true },
+            material: Material::moon(),
         },
         Entity {
             name: "planet_rocky3",
             translation: Vector3::new(0.0, 0.0, 0.0),
             rotation: Vector3::new(0.0, 0.0, 0.0),
             scale: 1.0,
-            motion: Motion::Orbit { 
-                center: Vector3::new(0.0, 0.0, 0.0), radius: 50.0, angular_speed: 0.65, phase: 0.0 
+            // Eccentric and tilted: speeds up near periapsis and dips below the other orbits
+            motion: Motion::Kepler {
+                parent: None,
+                orbit: KeplerOrbit {
+                    semi_major_axis: 50.0, eccentricity: 0.3, inclination: 0.25,
+                    ascending_node: 0.8, arg_periapsis: 1.2, mean_anomaly_epoch: 0.0,
+                    mean_motion: 0.65,
+                },
             },
+            mass: 0.05,
             vertices: generate_uv_sphere(0.8, 16, 24),
-            vshader: VertexShader::DisplaceSpherical { amp: 0.08, freq: 2.5, octaves: 4, lacunarity: 2.0, gain: 0.5, time_amp: 0.2 },
+            uvs: Vec::new(),
+            vshader: VertexShader::DisplaceSpherical { amp: 0.08, freq: 2.5, octaves: 4, lacunarity: 2.0, gain: 0.5, time_amp: 0.2, basis: NoiseBasis::Ridged, seed: 0 },
+            baked: DisplacementCache::default(),
+            spin: Vector3::new(0.0, 1.2, 0.0),
+            face_tangent: false,
+            shader: ShaderConfig { enabled: true },
+            material: rocky3_material,
+        },
+        // The ship we will follow, circling planet_rocky nose-first
+        Entity {
+            name: "ship",
+            translation: Vector3::new(0.0, 0.0, 0.0),
+            rotation: Vector3::new(0.0, 0.0, 0.0),
+            scale: 0.2,
+            motion: Motion::OrbitAround {
+                parent: "planet_rocky",
+                radius: 2.0,
+                angular_speed: 1.5,
+                phase: 0.0,
+            },
+            mass: 0.0,
+            vertices: ship_vertices,
+            uvs: ship_uvs,
+            vshader: VertexShader::Identity,
+            baked: DisplacementCache::default(),
+            spin: Vector3::new(0.0, 0.0, 0.0),
+            face_tangent: true,
+            shader: ShaderConfig { enabled: true },
+            material: ship_material,
+        },
+        // Helper shells last, so the number keys keep selecting the bodies themselves
+        // Corona: additive shell around the sun, sized to the corona's shell_scale
+        Entity {
+            name: "sun_corona",
+            translation: Vector3::new(0.0, 0.0, 0.0),
+            rotation: Vector3::new(0.0, 0.0, 0.0),
+            scale: 1.0,
+            motion: Motion::OrbitAround {
+                parent: "sun",
+                radius: 0.0,
+                angular_speed: 0.0,
+                phase: 0.0,
+            },
+            mass: 0.0,
+            vertices: generate_uv_sphere(3.0 * CoronaParams::sun().shell_scale, 24, 32),
+            uvs: Vec::new(),
+            vshader: VertexShader::Identity,
+            baked: DisplacementCache::default(),
+            spin: Vector3::new(0.0, 0.0, 0.0),
+            face_tangent: false,
+            shader: ShaderConfig { enabled: true },
+            material: Material::corona_at(sun_temperature),
+        },
+        // Cloud shell: follows planet_rocky with the same spin; the layer drifts on its own
+        Entity {
+            name: "planet_rocky_clouds",
+            translation: Vector3::new(0.0, 0.0, 0.0),
+            rotation: Vector3::new(0.0, 0.0, 0.0),
+            scale: 1.0,
+            motion: Motion::OrbitAround {
+                parent: "planet_rocky",
+                radius: 0.0,
+                angular_speed: 0.0,
+                phase: 0.0,
+            },
+            mass: 0.0,
+            vertices: generate_uv_sphere(0.8 * earth_clouds.shell_scale, 24, 32),
+            uvs: Vec::new(),
+            vshader: VertexShader::Identity,
+            baked: DisplacementCache::default(),
             spin: Vector3::new(0.0, 1.2, 0.0),
             face_tangent: false,
-            shader: ShaderConfig { enabled: true, layer1: true, layer2: true, layer3: true, layer4: true },
-            material: Material::rocky(),
+            shader: ShaderConfig { enabled: true },
+            material: Material::clouds(earth_clouds),
         },
     ];
 
+    // Per-body seeds from the entity name: unique look per body, reproducible across runs.
+    // XOR keeps any seed already set on a material preset meaningful.
+    for e in entities.iter_mut() {
+        let seed = derive_seed(scene_seed, e.name);
+        e.material.seed ^= seed;
+        e.vshader = e.vshader.clone().with_seed(seed);
+    }
+
+    // Bake the static vertex displacement (and normals) once per entity
+    for e in entities.iter_mut() {
+        e.baked = DisplacementCache::bake(&e.vertices, &e.uvs, &e.vshader);
+    }
+
+    // `wireframe bake <entity> [width] [time] [prefix]`: write the entity's maps and exit
+    if args.get(1).map(String::as_str) == Some("bake") {
+        if let Err(e) = bake_entity(&entities, &args[2..]) {
+            eprintln!("bake: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // `wireframe nbody [seconds] [dt] [integrator]`: compare the integrators' drift and exit
+    if args.get(1).map(String::as_str) == Some("nbody") {
+        if let Err(e) = nbody_report(&entities, &args[2..]) {
+            eprintln!("nbody: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let (mut window, raylib_thread) = raylib::init()
+        .size(window_width, window_height)
+        .title("Wireframe")
+        .log_level(TraceLogLevel::LOG_WARNING)
+        .build();
+
     let mut shader_view = ShaderViewMode::All;
     let mut selected_entity: usize = 0; // index to control via keyboard
+    let mut selected_layer: usize = 0;  // layer of the selected entity for the L toggle
 
     let mut camera = Camera::new(
         Vector3::new(0.0, 0.0, 70.0),
@@ -410,6 +1039,9 @@ fn main() {
     );
 
     let start_time = Instant::now();
+    let mut nbody: Option<NBodyScene> = None;
+    let mut integrator = Integrator::Leapfrog;
+    let mut last_time = 0.0f32;
 
     while !window.window_should_close() {
         framebuffer.clear();
@@ -426,24 +1058,41 @@ fn main() {
         if window.is_key_pressed(KeyboardKey::KEY_SEVEN) { selected_entity = 6.min(entities.len().saturating_sub(1)); }
         if window.is_key_pressed(KeyboardKey::KEY_EIGHT) { selected_entity = 7.min(entities.len().saturating_sub(1)); }
         if window.is_key_pressed(KeyboardKey::KEY_NINE)  { selected_entity = 8.min(entities.len().saturating_sub(1)); }
+        // Tab walks the whole list, including what the number keys can't reach
+        if window.is_key_pressed(KeyboardKey::KEY_TAB)   { selected_entity = (selected_entity + 1) % entities.len(); }
 
         // Toggle per-entity master enabled with 'T'
         if window.is_key_pressed(KeyboardKey::KEY_T) {
             let e = &mut entities[selected_entity];
             e.shader.enabled = !e.shader.enabled;
         }
-        // Toggle layers with G/H/J/K
-        if window.is_key_pressed(KeyboardKey::KEY_G) {
-            entities[selected_entity].shader.layer1 = !entities[selected_entity].shader.layer1;
+        // Pick a layer of the selected entity with [ / ] and toggle it with L;
+        // G/H/J/K toggle the first four layers directly
+        let layer_count = entities[selected_entity].material.layers.len();
+        if window.is_key_pressed(KeyboardKey::KEY_RIGHT_BRACKET) && layer_count > 0 {
+            selected_layer = (selected_layer + 1) % layer_count;
         }
-        if window.is_key_pressed(KeyboardKey::KEY_H) {
-            entities[selected_entity].shader.layer2 = !entities[selected_entity].shader.layer2;
+        if window.is_key_pressed(KeyboardKey::KEY_LEFT_BRACKET) && layer_count > 0 {
+            selected_layer = (selected_layer + layer_count - 1) % layer_count;
         }
-        if window.is_key_pressed(KeyboardKey::KEY_J) {
-            entities[selected_entity].shader.layer3 = !entities[selected_entity].shader.layer3;
+        selected_layer = selected_layer.min(layer_count.saturating_sub(1));
+        let mut toggle = None;
+        if window.is_key_pressed(KeyboardKey::KEY_L) { toggle = Some(selected_layer); }
+        if window.is_key_pressed(KeyboardKey::KEY_G) { toggle = Some(0); }
+        if window.is_key_pressed(KeyboardKey::KEY_H) { toggle = Some(1); }
+        if window.is_key_pressed(KeyboardKey::KEY_J) { toggle = Some(2); }
+        if window.is_key_pressed(KeyboardKey::KEY_K) { toggle = Some(3); }
+        if let Some(layer) = toggle.and_then(|i| entities[selected_entity].material.layers.get_mut(i)) {
+            layer.enabled = !layer.enabled;
         }
-        if window.is_key_pressed(KeyboardKey::KEY_K) {
-            entities[selected_entity].shader.layer4 = !entities[selected_entity].shader.layer4;
+        // Cycle the selected entity's shading with 'M': Phong -> Flat -> Gouraud
+        if window.is_key_pressed(KeyboardKey::KEY_M) {
+            let m = &mut entities[selected_entity].material;
+            m.shading = match m.shading {
+                ShadingMode::Phong => ShadingMode::Flat,
+                ShadingMode::Flat => ShadingMode::Gouraud,
+                ShadingMode::Gouraud => ShadingMode::Phong,
+            };
         }
         // Solo view: press 'Y' to solo the selected entity; 'U' to show all
         if window.is_key_pressed(KeyboardKey::KEY_Y) {
@@ -458,47 +1107,34 @@ fn main() {
         let resolution = Vector2::new(window_width as f32, window_height as f32);
 
         // --- Update entity motions ---
-        use std::collections::HashMap;
-        let index_by_name: HashMap<&'static str, usize> = entities.iter().enumerate().map(|(i,e)| (e.name, i)).collect();
-        
-        // Pass 1: update world-centered orbits and statics
-        for i in 0..entities.len() {
-            match entities[i].motion {
-                Motion::Static => { /* no-op */ }
-                Motion::Orbit { center, radius, angular_speed, phase } => {
-                    let theta = phase + angular_speed * time;
-                    entities[i].translation.x = center.x + radius * theta.cos();
-                    entities[i].translation.z = center.z + radius * theta.sin();
-                    entities[i].translation.y = center.y;
-                    // entities[i].rotation.y = -theta; // removed
-                }
-                Motion::OrbitAround { .. } => { /* defer to pass 2 */ }
+        // 'N' toggles the N-body simulation, started from the current analytic positions; 'I' cycles its integrator
+        if window.is_key_pressed(KeyboardKey::KEY_N) {
+            nbody = match nbody {
+                Some(_) => None,
+                None => Some(NBodyScene::start(&entities, time, integrator, NBODY_DT)),
+            };
+        }
+        if window.is_key_pressed(KeyboardKey::KEY_I) {
+            integrator = integrator.next();
+            if let Some(scene) = nbody.as_mut() {
+                scene.sim.set_integrator(integrator);
             }
         }
-        
-        // Pass 2: update children that orbit around a parent (world-axes offset around parent's position)
-        for i in 0..entities.len() {
-            if let Motion::OrbitAround { parent, radius, angular_speed, phase } = entities[i].motion.clone() {
-                if let Some(&pi) = index_by_name.get(parent) {
-                    let parent_pos = entities[pi].translation;
-                    let theta = phase + angular_speed * time;
-
-                    if radius == 0.0 {
-                        // Keep centered on parent; allow spin-in-place via rotation if desired
-                        entities[i].translation = parent_pos;
-                        // entities[i].rotation.y = -theta; // removed
-                    } else {
-                        // Orbit around parent in world axes (no coupling to parent's heading)
-                        let world_offset = Vector3::new(radius * theta.cos(), 0.0, radius * theta.sin());
-                        entities[i].translation = Vector3::new(
-                            parent_pos.x + world_offset.x,
-                            parent_pos.y + world_offset.y,
-                            parent_pos.z + world_offset.z,
-                        );
-                        // entities[i].rotation.y = -theta; // removed
-                    }
-                }
+        let pinned = match nbody.as_mut() {
+            Some(scene) => {
+                scene.sim.advance(((time - last_time) as f64).min(NBODY_MAX_FRAME));
+                scene.pinned(entities.len())
             }
+            None => Vec::new(),
+        };
+        last_time = time;
+        let positions = motion_positions(&entities, time, &pinned);
+        for (e, p) in entities.iter_mut().zip(positions) {
+            e.translation = p;
+        }
+        // Animated vertex shaders: drift over the baked mesh
+        for e in entities.iter_mut() {
+            e.baked.animate(&e.vshader, time);
         }
 
         // --- Follow camera: lock target to sun position ---
@@ -508,63 +1144,109 @@ fn main() {
 
         let view = camera.get_view_matrix();
 
+        // The first star in the scene lights everything, in its blackbody color
+        let (light_pos, light_color) = entities.iter()
+            .find_map(|e| e.material.star.map(|s| (e.translation, blackbody_linear(s.temperature))))
+            .unwrap_or((Vector3::zero(), Vector3::one()));
+        let uniforms = Uniforms { time, resolution, camera_pos: camera.eye, light_pos, light_color, light_dir_obj: Vector3::zero(), model: Matrix::identity() };
         // --- Render all entities ---
-        for e in &entities {
-            // Compute effective rotation (do not mutate e.rotation):
-            let mut rot = e.rotation;
-
-            // Add tangent-facing yaw from orbital motion if requested
-            if e.face_tangent {
-                match e.motion {
-                    Motion::Orbit { angular_speed, phase, .. } => {
-                        let theta = phase + angular_speed * time;
-                        rot.y += -theta;
-                    }
-                    Motion::OrbitAround { angular_speed, phase, .. } => {
-                        let theta = phase + angular_speed * time;
-                        rot.y += -theta;
+        // Opaque bodies first, then the sky behind them, then transparent shells (clouds, coronae) on top of both
+        let (w, h) = (window_width as f32, window_height as f32);
+        for pass in [BlendMode::Opaque, BlendMode::Alpha, BlendMode::Additive] {
+            if pass == BlendMode::Alpha {
+                // --- Sky: only where no geometry was drawn ---
+                framebuffer.fill_background(|x, y| {
+                    let dir = camera.ray_direction(x as f32, y as f32, w, h, fov_y);
+                    vec3_to_color(sky.sample(dir))
+                });
+            }
+            for (i, e) in entities.iter().enumerate().filter(|(_, e)| e.material.blend == pass) {
+                // Compute effective rotation (do not mutate e.rotation):
+                let mut rot = e.rotation;
+
+                // Add tangent-facing yaw from orbital motion if requested
+                if e.face_tangent {
+                    let simulated = matches!(pinned.get(i), Some(Some(_)));
+                    match e.motion {
+                        // Simulated bodies have no phase angle: use where they are around their parent
+                        _ if simulated => {
+                            if let Some(pi) = orbit_parent(&entities, i) {
+                                let d = e.translation - entities[pi].translation;
+                                rot.y += -d.z.atan2(d.x);
+                            }
+                        }
+                        Motion::Orbit { angular_speed, phase, .. } => {
+                            let theta = phase + angular_speed * time;
+                            rot.y += -theta;
+                        }
+                        Motion::OrbitAround { angular_speed, phase, .. } => {
+                            let theta = phase + angular_speed * time;
+                            rot.y += -theta;
+                        }
+                        Motion::Kepler { orbit, .. } => {
+                            // Position angle around the parent, tilted orbits included
+                            let p = orbit.position(time);
+                            rot.y += -p.z.atan2(p.x);
+                        }
+                        Motion::Static => {}
                     }
-                    Motion::Static => {}
                 }
-            }
 
-            // Add self spin (axial rotation)
-            rot.x += e.spin.x * time;
-            rot.y += e.spin.y * time;
-            rot.z += e.spin.z * time;
+                // Add self spin (axial rotation)
+                rot.x += e.spin.x * time;
+                rot.y += e.spin.y * time;
+                rot.z += e.spin.z * time;
 
-            let apply_shader = match shader_view {
-                ShaderViewMode::All => e.shader.enabled,
-                ShaderViewMode::Solo(idx) => {
-                    // Only selected entity uses its shader; others fallback
-                    std::ptr::eq(e, &entities[idx]) || (entities[idx].name == e.name)
-                }
-            };
+                let apply_shader = match shader_view {
+                    ShaderViewMode::All => e.shader.enabled,
+                    ShaderViewMode::Solo(idx) => {
+                        // Only selected entity uses its shader; others fallback
+                        std::ptr::eq(e, &entities[idx]) || (entities[idx].name == e.name)
+                    }
+                };
 
-            render(
-                &mut framebuffer,
-                e.translation,
-                e.scale,
-                rot,
-                &e.vertices,
-                &view,
-                &projection,
-                &viewport,
-                time,
-                resolution,
-                &e.vshader,
-                apply_shader,
-                (e.shader.layer1, e.shader.layer2, e.shader.layer3, e.shader.layer4),
-                &e.material,
-            );
+                render(
+                    &mut framebuffer,
+                    e.translation,
+                    e.scale,
+                    rot,
+                    &e.baked.positions,
+                    &VertexAttributes {
+                        normals: &e.baked.normals,
+                        uvs: &e.uvs,
+                        tangents: &e.baked.tangents,
+                        bitangents: &e.baked.bitangents,
+                    },
+                    &view,
+                    &projection,
+                    &viewport,
+                    &uniforms,
+                    apply_shader,
+                    &e.material,
+                );
+            }
         }
-
         let e = &entities[selected_entity];
+        // Layer list, e.g. "surface [bands] -noise": brackets = selected, '-' = disabled
+        let layer_list: Vec<String> = e.material.layers.iter().enumerate().map(|(i, l)| {
+            let name = format!("{}{}", if l.enabled { "" } else { "-" }, l.kind.name());
+            if i == selected_layer { format!("[{}]", name) } else { name }
+        }).collect();
+        // N-body status: integrator plus energy / momentum drift since it started (or switched)
+        let physics = match &nbody {
+            Some(scene) => {
+                let drift = scene.sim.drift();
+                format!("{} dE/E={:.1e} dP={:.1e}", integrator.name(), drift.relative_energy, drift.momentum)
+            }
+            None => format!("off ({})", integrator.name()),
+        };
         window.set_window_title(&raylib_thread, &format!(
-            "Wireframe | Selected: {} | T=toggle enabled:{} | G/H/J/K L1/L2/L3/L4: {}/{}/{}/{} | Y=Solo, U=All",
+            "Wireframe | Selected: {} | T=toggle enabled:{} | M=shading:{:?} | Layers ([/] pick, L toggle): {} | N=n-body: {}, I=integrator | Y=Solo, U=All",
             e.name,
             e.shader.enabled,
-            e.shader.layer1, e.shader.layer2, e.shader.layer3, e.shader.layer4
+            e.material.shading,
+            layer_list.join(" "),
+            physics
         ));
 
         framebuffer.swap_buffers(&mut window, &raylib_thread);
@@ -0,0 +1,353 @@
+// src/nbody.rs
+use raylib::prelude::*;
+
+/// Time-stepping scheme for `NBody`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Integrator {
+    Leapfrog, // kick-drift-kick velocity Verlet: symplectic, energy error stays bounded
+    Rk4,      // classic 4th-order Runge-Kutta: accurate per step, energy slowly drifts
+    Rk45,     // Dormand-Prince 5(4) with adaptive substeps inside each fixed step
+}
+
+impl Integrator {
+    pub const ALL: [Integrator; 3] = [Integrator::Leapfrog, Integrator::Rk4, Integrator::Rk45];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Integrator::Leapfrog => "leapfrog",
+            Integrator::Rk4 => "rk4",
+            Integrator::Rk45 => "rk45",
+        }
+    }
+
+    pub fn named(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|i| i.name() == name)
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Integrator::Leapfrog => Integrator::Rk4,
+            Integrator::Rk4 => Integrator::Rk45,
+            Integrator::Rk45 => Integrator::Leapfrog,
+        }
+    }
+}
+
+/// Point mass in scene units (G = 1)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Body {
+    pub position: Vector3,
+    pub velocity: Vector3,
+    pub mass: f32,
+}
+
+/// Conserved quantities against their values when the simulation started
+#[derive(Clone, Copy, Debug)]
+pub struct Drift {
+    pub energy: f64,
+    pub relative_energy: f64, // |E - E0| / |E0|
+    pub momentum: f64,        // |P - P0| / sum(m |v0|)
+}
+
+const G: f64 = 1.0;
+const SOFTENING: f64 = 0.05;        // keeps close passes finite; the potential uses the same length
+const RK45_TOLERANCE: f64 = 1e-9;   // local error per substep, relative to the state's magnitude
+const RK45_MIN_SUBSTEP: f64 = 1e-7; // fraction of `dt`
+
+/// Newtonian gravity between point masses, integrated in f64 with a fixed timestep.
+/// State is flat: 6 numbers per body (position then velocity).
+pub struct NBody {
+    pub integrator: Integrator,
+    pub dt: f64,
+    pub substeps: u64, // steps actually taken: more than the fixed ones when RK45 subdivides
+    masses: Vec<f64>,
+    state: Vec<f64>,
+    pending: f64,   // simulated time owed but shorter than one step
+    rk45_h: f64,    // last accepted RK45 substep, reused as the next guess
+    initial_energy: f64,
+    initial_momentum: [f64; 3],
+    momentum_scale: f64,
+}
+
+impl NBody {
+    pub fn new(bodies: &[Body], integrator: Integrator, dt: f32) -> Self {
+        let mut state = Vec::with_capacity(bodies.len() * 6);
+        for b in bodies {
+            state.extend([b.position.x, b.position.y, b.position.z, b.velocity.x, b.velocity.y, b.velocity.z].map(f64::from));
+        }
+        let mut sim = NBody {
+            integrator,
+            dt: dt as f64,
+            substeps: 0,
+            masses: bodies.iter().map(|b| b.mass as f64).collect(),
+            state,
+            pending: 0.0,
+            rk45_h: dt as f64,
+            initial_energy: 0.0,
+            initial_momentum: [0.0; 3],
+            momentum_scale: 0.0,
+        };
+        sim.rebase();
+        sim
+    }
+
+    /// Switches scheme mid-run; drift is measured again from this point
+    pub fn set_integrator(&mut self, integrator: Integrator) {
+        self.integrator = integrator;
+        self.rk45_h = self.dt;
+        self.rebase();
+    }
+
+    fn rebase(&mut self) {
+        self.initial_energy = self.energy();
+        self.initial_momentum = self.momentum();
+        self.momentum_scale = self.masses.iter().enumerate()
+            .map(|(i, m)| m * norm(&self.state[i * 6 + 3..i * 6 + 6]))
+            .sum::<f64>()
+            .max(1e-12);
+    }
+
+    pub fn position(&self, i: usize) -> Vector3 {
+        let s = &self.state[i * 6..i * 6 + 3];
+        Vector3::new(s[0] as f32, s[1] as f32, s[2] as f32)
+    }
+
+    /// Runs as many fixed steps as fit in `duration` (plus what earlier calls left over)
+    pub fn advance(&mut self, duration: f64) {
+        self.pending += duration;
+        while self.pending >= self.dt {
+            self.step();
+            self.pending -= self.dt;
+        }
+    }
+
+    pub fn step(&mut self) {
+        match self.integrator {
+            Integrator::Leapfrog => self.leapfrog(),
+            Integrator::Rk4 => self.rk4(),
+            Integrator::Rk45 => self.rk45(),
+        }
+    }
+
+    fn leapfrog(&mut self) {
+        let (dt, n) = (self.dt, self.masses.len());
+        let kick = |state: &mut Vec<f64>, acc: &[f64]| {
+            for i in 0..n {
+                for k in 0..3 {
+                    state[i * 6 + 3 + k] += 0.5 * dt * acc[i * 3 + k];
+                }
+            }
+        };
+        let acc = self.accelerations(&self.state);
+        kick(&mut self.state, &acc);
+        for i in 0..n {
+            for k in 0..3 {
+                self.state[i * 6 + k] += dt * self.state[i * 6 + 3 + k];
+            }
+        }
+        let acc = self.accelerations(&self.state);
+        kick(&mut self.state, &acc);
+        self.substeps += 1;
+    }
+
+    fn rk4(&mut self) {
+        let h = self.dt;
+        let y = &self.state;
+        let k1 = self.derivative(y);
+        let k2 = self.derivative(&combine(y, h, &[(0.5, &k1)]));
+        let k3 = self.derivative(&combine(y, h, &[(0.5, &k2)]));
+        let k4 = self.derivative(&combine(y, h, &[(1.0, &k3)]));
+        self.state = combine(y, h, &[(1.0 / 6.0, &k1), (1.0 / 3.0, &k2), (1.0 / 3.0, &k3), (1.0 / 6.0, &k4)]);
+        self.substeps += 1;
+    }
+
+    /// Dormand-Prince 5(4): substeps sized so the embedded error estimate stays under the
+    /// tolerance, together landing exactly on the end of the fixed step
+    fn rk45(&mut self) {
+        // Stage weights (gravity doesn't depend on t, so the stage times aren't needed)
+        const A: [&[f64]; 6] = [
+            &[1.0 / 5.0],
+            &[3.0 / 40.0, 9.0 / 40.0],
+            &[44.0 / 45.0, -56.0 / 15.0, 32.0 / 9.0],
+            &[19372.0 / 6561.0, -25360.0 / 2187.0, 64448.0 / 6561.0, -212.0 / 729.0],
+            &[9017.0 / 3168.0, -355.0 / 33.0, 46732.0 / 5247.0, 49.0 / 176.0, -5103.0 / 18656.0],
+            &[35.0 / 384.0, 0.0, 500.0 / 1113.0, 125.0 / 192.0, -2187.0 / 6784.0, 11.0 / 84.0],
+        ];
+        // The last row of A is the 5th-order solution; these are the embedded 4th-order weights
+        const B4: [f64; 7] = [5179.0 / 57600.0, 0.0, 7571.0 / 16695.0, 393.0 / 640.0, -92097.0 / 339200.0, 187.0 / 2100.0, 1.0 / 40.0];
+
+        let min_h = RK45_MIN_SUBSTEP * self.dt;
+        let mut left = self.dt;
+        let mut h = self.rk45_h.min(left);
+        while left > 1e-12 * self.dt {
+            let y = &self.state;
+            let mut k = vec![self.derivative(y)];
+            let mut y5 = Vec::new();
+            for row in A {
+                let terms: Vec<(f64, &Vec<f64>)> = row.iter().copied().zip(&k).collect();
+                y5 = combine(y, h, &terms);
+                k.push(self.derivative(&y5));
+            }
+            let terms: Vec<(f64, &Vec<f64>)> = B4.iter().copied().zip(&k).collect();
+            let y4 = combine(y, h, &terms);
+
+            let error = y5.iter().zip(&y4).zip(y)
+                .map(|((a, b), s)| (a - b).abs() / (RK45_TOLERANCE * (1.0 + s.abs())))
+                .fold(0.0, f64::max);
+            if error <= 1.0 || h <= min_h {
+                self.state = y5;
+                left -= h;
+                self.substeps += 1;
+                self.rk45_h = h;
+            }
+            let grow = if error > 0.0 { 0.9 * error.powf(-0.2) } else { 5.0 };
+            h = (h * grow.clamp(0.2, 5.0)).max(min_h).min(left);
+        }
+    }
+
+    fn derivative(&self, y: &[f64]) -> Vec<f64> {
+        let acc = self.accelerations(y);
+        let mut dy = vec![0.0; y.len()];
+        for i in 0..self.masses.len() {
+            for k in 0..3 {
+                dy[i * 6 + k] = y[i * 6 + 3 + k];
+                dy[i * 6 + 3 + k] = acc[i * 3 + k];
+            }
+        }
+        dy
+    }
+
+    /// Softened pairwise gravity, 3 numbers per body
+    fn accelerations(&self, y: &[f64]) -> Vec<f64> {
+        let n = self.masses.len();
+        let mut acc = vec![0.0; n * 3];
+        for i in 0..n {
+            for j in i + 1..n {
+                let d = [y[j * 6] - y[i * 6], y[j * 6 + 1] - y[i * 6 + 1], y[j * 6 + 2] - y[i * 6 + 2]];
+                let r2 = d[0] * d[0] + d[1] * d[1] + d[2] * d[2] + SOFTENING * SOFTENING;
+                let inv_r3 = G / (r2 * r2.sqrt());
+                for k in 0..3 {
+                    acc[i * 3 + k] += self.masses[j] * inv_r3 * d[k];
+                    acc[j * 3 + k] -= self.masses[i] * inv_r3 * d[k];
+                }
+            }
+        }
+        acc
+    }
+
+    /// Kinetic plus (softened) potential energy
+    pub fn energy(&self) -> f64 {
+        let (y, n) = (&self.state, self.masses.len());
+        let mut e = 0.0;
+        for i in 0..n {
+            let v = norm(&y[i * 6 + 3..i * 6 + 6]);
+            e += 0.5 * self.masses[i] * v * v;
+            for j in i + 1..n {
+                let d = [y[j * 6] - y[i * 6], y[j * 6 + 1] - y[i * 6 + 1], y[j * 6 + 2] - y[i * 6 + 2]];
+                let r = (norm(&d).powi(2) + SOFTENING * SOFTENING).sqrt();
+                e -= G * self.masses[i] * self.masses[j] / r;
+            }
+        }
+        e
+    }
+
+    pub fn momentum(&self) -> [f64; 3] {
+        let mut p = [0.0; 3];
+        for (i, m) in self.masses.iter().enumerate() {
+            for (k, pk) in p.iter_mut().enumerate() {
+                *pk += m * self.state[i * 6 + 3 + k];
+            }
+        }
+        p
+    }
+
+    pub fn drift(&self) -> Drift {
+        let energy = self.energy();
+        let p = self.momentum();
+        let dp = [p[0] - self.initial_momentum[0], p[1] - self.initial_momentum[1], p[2] - self.initial_momentum[2]];
+        Drift {
+            energy,
+            relative_energy: (energy - self.initial_energy).abs() / self.initial_energy.abs().max(1e-12),
+            momentum: norm(&dp) / self.momentum_scale,
+        }
+    }
+}
+
+/// y + h * sum(w * k)
+fn combine(y: &[f64], h: f64, terms: &[(f64, &Vec<f64>)]) -> Vec<f64> {
+    let mut out = y.to_vec();
+    for (w, k) in terms {
+        if *w != 0.0 {
+            for (o, kv) in out.iter_mut().zip(k.iter()) {
+                *o += h * w * kv;
+            }
+        }
+    }
+    out
+}
+
+fn norm(v: &[f64]) -> f64 {
+    v.iter().map(|x| x * x).sum::<f64>().sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: f32 = 1.0 / 240.0;
+
+    /// Heavy star and light planet on a circular orbit of radius 10, in the barycentre's frame
+    fn two_body(integrator: Integrator) -> NBody {
+        let (big, small, r) = (1000.0f32, 1.0f32, 10.0f32);
+        let v = ((big + small) / r).sqrt(); // relative circular speed (G = 1)
+        let total = big + small;
+        NBody::new(&[
+            Body { position: Vector3::new(-r * small / total, 0.0, 0.0), velocity: Vector3::new(0.0, 0.0, -v * small / total), mass: big },
+            Body { position: Vector3::new(r * big / total, 0.0, 0.0), velocity: Vector3::new(0.0, 0.0, v * big / total), mass: small },
+        ], integrator, DT)
+    }
+
+    /// Largest relative energy error within each of `orbits` consecutive periods
+    fn energy_error_per_orbit(sim: &mut NBody, orbits: usize) -> Vec<f64> {
+        let period = std::f64::consts::TAU * (1000.0f64 / 1001.0).sqrt();
+        let steps = (period / sim.dt).round() as usize;
+        (0..orbits).map(|_| {
+            (0..steps).fold(0.0f64, |worst, _| {
+                sim.step();
+                worst.max(sim.drift().relative_energy)
+            })
+        }).collect()
+    }
+
+    #[test]
+    fn leapfrog_energy_error_stays_bounded() {
+        let mut sim = two_body(Integrator::Leapfrog);
+        let errors = energy_error_per_orbit(&mut sim, 6);
+        let first = errors[0];
+        assert!(first < 1e-6, "first orbit: {}", first);
+        // Symplectic: the error oscillates instead of growing orbit after orbit
+        assert!(errors.iter().all(|&e| e < 2.0 * first), "{:?}", errors);
+    }
+
+    #[test]
+    fn rk45_energy_error_stays_below_its_tolerance() {
+        let mut sim = two_body(Integrator::Rk45);
+        let errors = energy_error_per_orbit(&mut sim, 3);
+        let worst = errors.iter().cloned().fold(0.0, f64::max);
+        assert!(worst < RK45_TOLERANCE, "{:?}", errors);
+    }
+
+    #[test]
+    fn momentum_is_conserved() {
+        for integrator in Integrator::ALL {
+            let mut sim = two_body(integrator);
+            // Give the pair a net velocity so conservation isn't trivially zero
+            sim.state[3 + 1] += 0.5;
+            sim.state[6 + 3 + 1] += 0.5;
+            sim.rebase();
+            sim.advance(5.0);
+            let d = sim.drift();
+            assert!(d.momentum < 1e-9, "{}: dP = {}", integrator.name(), d.momentum);
+        }
+    }
+}
@@ -4,6 +4,7 @@ use tobj;
 pub struct Obj {
     pub vertices: Vec<Vector3>,
     pub indices: Vec<u32>,
+    pub texcoords: Vec<Vector2>, // one per vertex, empty if the file has none
 }
 
 impl Obj {
@@ -12,6 +13,7 @@ impl Obj {
 
         let mut vertices = Vec::new();
         let mut indices = Vec::new();
+        let mut texcoords = Vec::new();
 
         for model in models {
             let mesh = &model.mesh;
@@ -24,10 +26,18 @@ impl Obj {
                 let position = Vector3::new(x, y, z);
                 vertices.push(position);
             }
+            // GPU_LOAD_OPTIONS gives a single index, so texcoords line up with positions
+            if mesh.texcoords.len() == num_vertices * 2 {
+                texcoords.extend(mesh.texcoords.chunks_exact(2).map(|t| Vector2::new(t[0], t[1])));
+            }
             indices.extend_from_slice(&mesh.indices);
         }
 
-        Ok(Obj { vertices, indices })
+        if texcoords.len() != vertices.len() {
+            texcoords.clear(); // some models lacked UVs; treat the mesh as untextured
+        }
+
+        Ok(Obj { vertices, indices, texcoords })
     }
 
     pub fn get_vertex_array(&self) -> Vec<Vector3> {
@@ -37,4 +47,12 @@ impl Obj {
         }
         vertex_array
     }
+
+    /// UVs matching `get_vertex_array` (empty if the mesh has none)
+    pub fn get_uv_array(&self) -> Vec<Vector2> {
+        if self.texcoords.is_empty() {
+            return Vec::new();
+        }
+        self.indices.iter().map(|&index| self.texcoords[index as usize]).collect()
+    }
 }
\ No newline at end of file
@@ -0,0 +1,98 @@
+// src/orbit.rs
+use raylib::prelude::*;
+use std::f32::consts::{PI, TAU};
+
+/// Classical orbital elements of an elliptical orbit. Angles in radians; the reference plane
+/// (ecliptic) is the scene's XZ plane with north along +Y, and orbits run the same way as
+/// `Motion::Orbit` with a positive `angular_speed`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KeplerOrbit {
+    pub semi_major_axis: f32,
+    pub eccentricity: f32,      // 0 = circle, toward 1 = long thin ellipse (must stay below 1)
+    pub inclination: f32,       // tilt of the orbit plane out of the ecliptic
+    pub ascending_node: f32,    // longitude of the ascending node (where it crosses the ecliptic going north)
+    pub arg_periapsis: f32,     // angle from the ascending node to periapsis, in the orbit plane
+    pub mean_anomaly_epoch: f32, // mean anomaly at time 0
+    pub mean_motion: f32,       // rad/s: average angular speed, TAU / period
+}
+
+const KEPLER_TOLERANCE: f32 = 1e-6;
+const KEPLER_MAX_ITERATIONS: u32 = 12;
+
+/// Eccentric anomaly E with E - e sin E = M (Newton's method)
+pub fn solve_kepler(mean_anomaly: f32, e: f32) -> f32 {
+    let m = (mean_anomaly + PI).rem_euclid(TAU) - PI; // [-pi, pi)
+    // Starting at pi converges for every M when the orbit is very eccentric
+    let mut ea = if e < 0.8 { m } else { PI.copysign(m) };
+    for _ in 0..KEPLER_MAX_ITERATIONS {
+        let step = (ea - e * ea.sin() - m) / (1.0 - e * ea.cos());
+        ea -= step;
+        if step.abs() < KEPLER_TOLERANCE {
+            break;
+        }
+    }
+    ea
+}
+
+impl KeplerOrbit {
+    /// Offset from the focus (the parent body) at `time`
+    pub fn position(&self, time: f32) -> Vector3 {
+        let e = self.eccentricity.clamp(0.0, 0.999);
+        let ea = solve_kepler(self.mean_anomaly_epoch + self.mean_motion * time, e);
+        // In the orbit plane, periapsis along +x
+        let x = self.semi_major_axis * (ea.cos() - e);
+        let y = self.semi_major_axis * (1.0 - e * e).sqrt() * ea.sin();
+        self.to_scene(x, y)
+    }
+
+    /// Rotates an orbit-plane vector by periapsis, inclination and node into scene axes
+    fn to_scene(&self, x: f32, y: f32) -> Vector3 {
+        let (so, co) = self.ascending_node.sin_cos();
+        let (sw, cw) = self.arg_periapsis.sin_cos();
+        let (si, ci) = self.inclination.sin_cos();
+        // Standard frame: ecliptic XY, north Z
+        let ex = (co * cw - so * sw * ci) * x + (-co * sw - so * cw * ci) * y;
+        let ey = (so * cw + co * sw * ci) * x + (-so * sw + co * cw * ci) * y;
+        let ez = (sw * si) * x + (cw * si) * y;
+        Vector3::new(ex, ez, ey)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn orbit(e: f32) -> KeplerOrbit {
+        KeplerOrbit {
+            semi_major_axis: 50.0, eccentricity: e, inclination: 0.25,
+            ascending_node: 0.8, arg_periapsis: 1.2, mean_anomaly_epoch: 0.0,
+            mean_motion: 0.65,
+        }
+    }
+
+    #[test]
+    fn kepler_equation_holds() {
+        for e in [0.0, 0.5, 0.95] {
+            for i in -40..=40 {
+                let m = i as f32 * 0.25; // about -1.6 to 1.6 turns, crossing the wrap at ±pi
+                let ea = solve_kepler(m, e);
+                let residual = (ea - e * ea.sin() - m + PI).rem_euclid(TAU) - PI;
+                assert!(residual.abs() < 1e-5, "e = {}, M = {}: E = {}, residual {}", e, m, ea, residual);
+            }
+        }
+    }
+
+    #[test]
+    fn starts_at_periapsis() {
+        for e in [0.0, 0.5, 0.95] {
+            let o = orbit(e);
+            let p = o.position(0.0);
+            let periapsis = o.to_scene(o.semi_major_axis * (1.0 - e), 0.0);
+            assert!((p - periapsis).length() < 1e-4, "e = {}: {:?} vs {:?}", e, p, periapsis);
+            assert!((p.length() - o.semi_major_axis * (1.0 - e)).abs() < 1e-4);
+        }
+        // Unrotated elements: periapsis lies on +x
+        let flat = KeplerOrbit { inclination: 0.0, ascending_node: 0.0, arg_periapsis: 0.0, ..orbit(0.5) };
+        assert!((flat.position(0.0) - Vector3::new(25.0, 0.0, 0.0)).length() < 1e-4);
+    }
+}
@@ -0,0 +1,239 @@
+// src/palette_fit.rs
+use raylib::prelude::*;
+use std::f64::consts::TAU;
+
+use crate::bake::write_png;
+use crate::shaders::palette_cosine;
+use crate::uniforms::color_to_vec3;
+
+/// Parameters of `palette_cosine`: color(t) = a + b * cos(TAU * (c * t + d)), per channel
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CosinePalette {
+    pub a: Vector3,
+    pub b: Vector3,
+    pub c: Vector3,
+    pub d: Vector3,
+}
+
+impl CosinePalette {
+    pub fn eval(&self, t: f32) -> Vector3 {
+        palette_cosine(t, self.a, self.b, self.c, self.d)
+    }
+}
+
+/// A palette fitted to the colors of an image, with what it was fitted to
+pub struct PaletteFit {
+    pub palette: CosinePalette,
+    pub target: Vec<Vector3>, // source colors at t = (k + 0.5) / len, darkest to brightest
+    pub rms: f32,             // root mean square error per channel against `target`
+}
+
+const BINS: usize = 64;
+const MAX_SAMPLES: usize = 1 << 16;
+// Frequencies tried per channel; above ~2 cycles the palette starts chasing noise in the bins
+const C_MIN: f64 = 0.1;
+const C_MAX: f64 = 2.0;
+const C_STEP: f64 = 0.01;
+
+fn luma(c: Vector3) -> f32 {
+    0.2126 * c.x + 0.7152 * c.y + 0.0722 * c.z
+}
+
+/// Orders the image's (opaque) pixels by brightness and fits a palette running dark -> bright
+/// through them: t is each pixel's brightness quantile, so frequent colors get more of the range.
+pub fn fit_image(path: &str) -> Result<PaletteFit, String> {
+    let image = Image::load_image(path).map_err(|e| format!("{}: {}", path, e))?;
+    let pixels = image.get_image_data();
+    let step = (pixels.len() / MAX_SAMPLES).max(1);
+    let mut colors: Vec<Vector3> = pixels.iter().step_by(step).filter(|c| c.a >= 128).map(|&c| color_to_vec3(c)).collect();
+    if colors.len() < BINS {
+        return Err(format!("{}: needs at least {} opaque pixels, found {}", path, BINS, colors.len()));
+    }
+    colors.sort_by(|a, b| luma(*a).total_cmp(&luma(*b)));
+
+    // Mean color of each brightness quantile
+    let n = colors.len();
+    let target: Vec<Vector3> = (0..BINS)
+        .map(|k| {
+            let bin = &colors[k * n / BINS..(k + 1) * n / BINS];
+            bin.iter().fold(Vector3::zero(), |acc, &c| acc + c) / bin.len() as f32
+        })
+        .collect();
+    Ok(fit_samples(&target))
+}
+
+/// Least-squares cosine palette through colors sampled at evenly spaced t in 0..1
+pub fn fit_samples(target: &[Vector3]) -> PaletteFit {
+    let ts: Vec<f64> = (0..target.len()).map(|k| (k as f64 + 0.5) / target.len() as f64).collect();
+    let channel = |f: fn(&Vector3) -> f32| fit_channel(&ts, &target.iter().map(|c| f(c) as f64).collect::<Vec<_>>());
+    let (r, g, b) = (channel(|c| c.x), channel(|c| c.y), channel(|c| c.z));
+
+    let v = |i: usize| Vector3::new(r[i] as f32, g[i] as f32, b[i] as f32);
+    let palette = CosinePalette { a: v(0), b: v(1), c: v(2), d: v(3) };
+    let rms = ((r[4] + g[4] + b[4]) / (3 * target.len()) as f64).sqrt() as f32;
+    PaletteFit { palette, target: target.to_vec(), rms }
+}
+
+/// One channel: for a fixed frequency c, a + b cos(TAU (c t + d)) = a + p cos(TAU c t) + q sin(TAU c t)
+/// is linear in (a, p, q), so each c is an exact 3x3 least-squares solve. c is searched on a grid,
+/// then refined around the best one. Returns [a, b, c, d, squared error].
+fn fit_channel(ts: &[f64], ys: &[f64]) -> [f64; 5] {
+    let mean = ys.iter().sum::<f64>() / ys.len() as f64;
+    let flat = ys.iter().map(|y| (y - mean).powi(2)).sum::<f64>();
+    let mut best = [mean, 0.0, 1.0, 0.0, flat];
+
+    let try_c = |c: f64, best: &mut [f64; 5]| {
+        if let Some((a, p, q, err)) = solve_at(ts, ys, c) {
+            if err < best[4] {
+                let b = p.hypot(q);
+                let d = ((-q).atan2(p) / TAU).rem_euclid(1.0);
+                *best = [a, b, c, d, err];
+            }
+        }
+    };
+    let coarse = ((C_MAX - C_MIN) / C_STEP).round() as usize;
+    for i in 0..=coarse {
+        try_c(C_MIN + i as f64 * C_STEP, &mut best);
+    }
+    let center = best[2];
+    for i in -20..=20 {
+        try_c((center + i as f64 * C_STEP / 20.0).clamp(C_MIN, C_MAX), &mut best);
+    }
+    best
+}
+
+/// Normal equations for y ~ a + p cos(TAU c t) + q sin(TAU c t); None if degenerate
+fn solve_at(ts: &[f64], ys: &[f64], c: f64) -> Option<(f64, f64, f64, f64)> {
+    let mut m = [[0.0f64; 3]; 3];
+    let mut r = [0.0f64; 3];
+    for (&t, &y) in ts.iter().zip(ys) {
+        let basis = [1.0, (TAU * c * t).cos(), (TAU * c * t).sin()];
+        for i in 0..3 {
+            for j in 0..3 {
+                m[i][j] += basis[i] * basis[j];
+            }
+            r[i] += basis[i] * y;
+        }
+    }
+    let [a, p, q] = solve3(m, r)?;
+    let err = ts.iter().zip(ys).map(|(&t, &y)| {
+        let fit = a + p * (TAU * c * t).cos() + q * (TAU * c * t).sin();
+        (fit - y).powi(2)
+    }).sum();
+    Some((a, p, q, err))
+}
+
+/// Cramer's rule
+fn solve3(m: [[f64; 3]; 3], r: [f64; 3]) -> Option<[f64; 3]> {
+    let det = |m: [[f64; 3]; 3]| {
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    };
+    let d = det(m);
+    if d.abs() < 1e-12 {
+        return None;
+    }
+    let column = |k: usize| {
+        let mut mk = m;
+        for i in 0..3 {
+            mk[i][k] = r[i];
+        }
+        det(mk) / d
+    };
+    Some([column(0), column(1), column(2)])
+}
+
+/// Preset function name from a user-given one: `jupiter-2` -> `jupiter_2`, `2mass` -> `_2mass`.
+/// Fails on names that are empty after conversion or are Rust keywords.
+pub fn preset_ident(name: &str) -> Result<String, String> {
+    const KEYWORDS: &[&str] = &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern", "false",
+        "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return",
+        "self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use", "where", "while", "_",
+    ];
+    let mut ident: String = name.trim().chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+    if ident.starts_with(|c: char| c.is_ascii_digit()) {
+        ident.insert(0, '_');
+    }
+    if ident.is_empty() || KEYWORDS.contains(&ident.as_str()) {
+        return Err(format!("`{}` can't be used as a preset name", name));
+    }
+    Ok(ident)
+}
+
+impl PaletteFit {
+    /// `Material` preset source using the fitted palette for both palettes of the Surface layer;
+    /// `name` must be an identifier (see `preset_ident`)
+    pub fn preset(&self, name: &str, source: &str) -> String {
+        let v = |v: Vector3| format!("Vector3::new({:.3}, {:.3}, {:.3})", v.x, v.y, v.z);
+        let p = &self.palette;
+        let pal = |k: u32| format!(
+            "pal{k}_a: {}, pal{k}_b: {}, pal{k}_c: {}, pal{k}_d: {},",
+            v(p.a), v(p.b), v(p.c), v(p.d),
+        );
+        format!(
+            "    /// Cosine palette fitted to `{source}` (rms error {rms:.4})\n    \
+             pub fn {name}() -> Self {{\n        \
+                 Self {{\n            \
+                     {pal1}\n            \
+                     {pal2}\n            \
+                     gas: None, // the palettes, not the band model, color the Surface\n            \
+                     ..Self::gaseous()\n        \
+                 }}\n    \
+             }}\n",
+            source = source, rms = self.rms, name = name, pal1 = pal(1), pal2 = pal(2),
+        )
+    }
+
+    /// Strip comparing the two: source colors on the top half, the fitted palette on the bottom
+    pub fn save_preview(&self, path: &str, width: u32, height: u32) -> Result<(), String> {
+        let bins = self.target.len();
+        let pixels = (0..width * height).map(|i| {
+            let (x, y) = (i % width, i / width);
+            let t = (x as f32 + 0.5) / width as f32;
+            if y < height / 2 {
+                self.target[((t * bins as f32) as usize).min(bins - 1)]
+            } else {
+                self.palette.eval(t)
+            }
+        });
+        write_png(path, width, height, pixels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preset_ident_sanitizes_names() {
+        assert_eq!(preset_ident("Jupiter-2").unwrap(), "jupiter_2");
+        assert_eq!(preset_ident("  my gas giant ").unwrap(), "my_gas_giant");
+        assert_eq!(preset_ident("2mass").unwrap(), "_2mass");
+        assert_eq!(preset_ident("fn_").unwrap(), "fn_");
+        for bad in ["", "   ", "fn", "Self", "type", "_"] {
+            assert!(preset_ident(bad).is_err(), "{:?}", bad);
+        }
+        assert_eq!(preset_ident("match").unwrap_err(), "`match` can't be used as a preset name");
+    }
+
+    #[test]
+    fn fit_recovers_a_cosine_palette() {
+        let known = CosinePalette {
+            a: Vector3::new(0.5, 0.4, 0.3),
+            b: Vector3::new(0.3, 0.2, 0.25),
+            c: Vector3::new(1.0, 0.7, 1.5),
+            d: Vector3::new(0.1, 0.3, 0.6),
+        };
+        let target: Vec<Vector3> = (0..BINS).map(|k| known.eval((k as f32 + 0.5) / BINS as f32)).collect();
+        let fit = fit_samples(&target);
+        assert!(fit.rms < 1e-4, "rms {}", fit.rms);
+        assert!((fit.palette.c - known.c).length() < 1e-3, "{:?}", fit.palette);
+        for t in [0.0, 0.3, 0.77, 1.0] {
+            assert!((fit.palette.eval(t) - known.eval(t)).length() < 1e-3, "t = {}", t);
+        }
+    }
+}
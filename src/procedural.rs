@@ -2,7 +2,8 @@
 use raylib::prelude::*;
 
 /// Generate a UV-sphere (triangulated) without external models.
-/// Returns a flat Vec of positions grouped in triangles (3-by-3).
+/// Returns a flat Vec of positions grouped in triangles (3-by-3), wound counter-clockwise seen
+/// from outside like OBJ meshes: `smooth_normals` takes its orientation from the winding.
 pub fn generate_uv_sphere(radius: f32, lat_segments: usize, lon_segments: usize) -> Vec<Vector3> {
     let lat = lat_segments.max(3);
     let lon = lon_segments.max(3);
@@ -27,9 +28,9 @@ pub fn generate_uv_sphere(radius: f32, lat_segments: usize, lon_segments: usize)
             let p10 = sph(radius, phi1, theta0);
             let p11 = sph(radius, phi1, theta1);
 
-            // Two triangles per quad (p00, p10, p11) and (p00, p11, p01)
-            out.push(p00); out.push(p10); out.push(p11);
-            out.push(p00); out.push(p11); out.push(p01);
+            // Two triangles per quad, counter-clockwise seen from outside (outward face normals)
+            out.push(p00); out.push(p11); out.push(p10);
+            out.push(p00); out.push(p01); out.push(p11);
         }
     }
     out
@@ -77,9 +78,59 @@ fn polar(r: f32, ang: f32) -> Vector3 {
     Vector3::new(r * ang.cos(), 0.0, r * ang.sin())
 }
 
+/// Smooth per-vertex normals for a triangle list (3-by-3): face normals are
+/// accumulated over vertices sharing a position (seams/poles included) and normalized.
+pub fn smooth_normals(positions: &[Vector3]) -> Vec<Vector3> {
+    use std::collections::HashMap;
+    let key = |p: Vector3| ((p.x * 1e4).round() as i32, (p.y * 1e4).round() as i32, (p.z * 1e4).round() as i32);
+
+    let mut acc: HashMap<(i32, i32, i32), Vector3> = HashMap::new();
+    for tri in positions.chunks_exact(3) {
+        // Area-weighted face normal (unnormalized cross product)
+        let n = (tri[1] - tri[0]).cross(tri[2] - tri[0]);
+        for p in tri {
+            *acc.entry(key(*p)).or_insert(Vector3::zero()) += n;
+        }
+    }
+    positions
+        .iter()
+        .map(|p| {
+            let n = acc[&key(*p)];
+            if n.length() > 1e-12 { n.normalized() } else { Vector3::new(0.0, 1.0, 0.0) }
+        })
+        .collect()
+}
+
+/// Per-vertex tangent and bitangent (directions of +u and +v on the surface) for a triangle
+/// list with UVs, one pair per triangle; empty if there are no UVs
+pub fn uv_tangents(positions: &[Vector3], uvs: &[Vector2]) -> (Vec<Vector3>, Vec<Vector3>) {
+    if uvs.len() != positions.len() {
+        return (Vec::new(), Vec::new());
+    }
+    let mut tangents = Vec::with_capacity(positions.len());
+    let mut bitangents = Vec::with_capacity(positions.len());
+    for (tri, uv) in positions.chunks_exact(3).zip(uvs.chunks_exact(3)) {
+        let (e1, e2) = (tri[1] - tri[0], tri[2] - tri[0]);
+        let (du1, dv1) = (uv[1].x - uv[0].x, uv[1].y - uv[0].y);
+        let (du2, dv2) = (uv[2].x - uv[0].x, uv[2].y - uv[0].y);
+        let det = du1 * dv2 - du2 * dv1;
+        let (t, b) = if det.abs() > 1e-12 {
+            let r = 1.0 / det;
+            ((e1 * dv2 - e2 * dv1) * r, (e2 * du1 - e1 * du2) * r)
+        } else {
+            (e1, e2) // degenerate UVs: any frame in the triangle plane
+        };
+        for _ in 0..3 {
+            tangents.push(t.normalized());
+            bitangents.push(b.normalized());
+        }
+    }
+    (tangents, bitangents)
+}
+
 // --- 3D Value Noise + FBM (no external crates) ---
 #[inline]
-fn hash_u32(mut x: u32) -> u32 {
+pub fn hash_u32(mut x: u32) -> u32 {
     // Thomas Wang mix
     x = x.wrapping_add(0x9E3779B9);
     x ^= x >> 15;
@@ -90,24 +141,74 @@ fn hash_u32(mut x: u32) -> u32 {
     x
 }
 
+/// Maps a hash to [0,1)
 #[inline]
-fn lattice_rand(i: i32, j: i32, k: i32) -> f32 {
-    let mut h = 1469598103u32; // FNV-like mix
-    h ^= i as u32; h = h.wrapping_mul(16777619);
-    h ^= j as u32; h = h.wrapping_mul(16777619);
-    h ^= k as u32; h = h.wrapping_mul(16777619);
-    let r = hash_u32(h);
-    (r as f32) / (u32::MAX as f32) // 0..1
+pub fn hash_to_unit(h: u32) -> f32 {
+    (h >> 8) as f32 / (1u32 << 24) as f32
 }
 
 #[inline]
-fn smoothstep(t: f32) -> f32 { t * t * (3.0 - 2.0 * t) }
+fn lattice_rand(i: i32, j: i32, k: i32, seed: u32) -> f32 {
+    (lattice_hash(i, j, k, seed) as f32) / (u32::MAX as f32) // 0..1
+}
 
 #[inline]
-fn lerp(a: f32, b: f32, t: f32) -> f32 { a + (b - a) * t }
+fn fade(t: f32) -> f32 { t * t * (3.0 - 2.0 * t) }
+
+#[inline]
+pub fn lerp(a: f32, b: f32, t: f32) -> f32 { a + (b - a) * t }
+
+/// GLSL smoothstep: 0 below `e0`, 1 above `e1`, Hermite in between
+#[inline]
+pub fn smoothstep(e0: f32, e1: f32, x: f32) -> f32 {
+    let t = ((x - e0) / (e1 - e0)).clamp(0.0, 1.0);
+    fade(t)
+}
+
+/// GLSL mix for colors
+#[inline]
+pub fn mix(a: Vector3, b: Vector3, t: f32) -> Vector3 {
+    a * (1.0 - t) + b * t
+}
+
+// --- Component-wise vec3 helpers (node graphs and shader expressions treat every value as a vec3) ---
+#[inline]
+pub fn splat(v: f32) -> Vector3 {
+    Vector3::new(v, v, v)
+}
+#[inline]
+pub fn xy(v: Vector3) -> Vector2 {
+    Vector2::new(v.x, v.y)
+}
+#[inline]
+pub fn each(a: Vector3, f: impl Fn(f32) -> f32) -> Vector3 {
+    Vector3::new(f(a.x), f(a.y), f(a.z))
+}
+#[inline]
+pub fn zip(a: Vector3, b: Vector3, f: impl Fn(f32, f32) -> f32) -> Vector3 {
+    Vector3::new(f(a.x, b.x), f(a.y, b.y), f(a.z, b.z))
+}
+#[inline]
+pub fn zip3(a: Vector3, b: Vector3, c: Vector3, f: impl Fn(f32, f32, f32) -> f32) -> Vector3 {
+    Vector3::new(f(a.x, b.x, c.x), f(a.y, b.y, c.y), f(a.z, b.z, c.z))
+}
+/// GLSL mix with a per-component `t`
+#[inline]
+pub fn mix3(a: Vector3, b: Vector3, t: Vector3) -> Vector3 {
+    zip3(a, b, t, lerp)
+}
+/// Per-component clamp; an inverted range collapses to `lo`
+#[inline]
+pub fn clamp3(x: Vector3, lo: Vector3, hi: Vector3) -> Vector3 {
+    zip3(x, lo, hi, |x, lo, hi| x.clamp(lo, hi.max(lo)))
+}
+#[inline]
+pub fn smoothstep3(e0: Vector3, e1: Vector3, x: Vector3) -> Vector3 {
+    zip3(e0, e1, x, smoothstep)
+}
 
 /// 3D value noise in [0,1]
-pub fn value_noise3(p: Vector3) -> f32 {
+pub fn value_noise3(p: Vector3, seed: u32) -> f32 {
     let x0 = p.x.floor() as i32;
     let y0 = p.y.floor() as i32;
     let z0 = p.z.floor() as i32;
@@ -115,18 +216,18 @@ pub fn value_noise3(p: Vector3) -> f32 {
     let y1 = y0 + 1;
     let z1 = z0 + 1;
 
-    let tx = smoothstep(p.x - x0 as f32);
-    let ty = smoothstep(p.y - y0 as f32);
-    let tz = smoothstep(p.z - z0 as f32);
+    let tx = fade(p.x - x0 as f32);
+    let ty = fade(p.y - y0 as f32);
+    let tz = fade(p.z - z0 as f32);
 
-    let c000 = lattice_rand(x0, y0, z0);
-    let c100 = lattice_rand(x1, y0, z0);
-    let c010 = lattice_rand(x0, y1, z0);
-    let c110 = lattice_rand(x1, y1, z0);
-    let c001 = lattice_rand(x0, y0, z1);
-    let c101 = lattice_rand(x1, y0, z1);
-    let c011 = lattice_rand(x0, y1, z1);
-    let c111 = lattice_rand(x1, y1, z1);
+    let c000 = lattice_rand(x0, y0, z0, seed);
+    let c100 = lattice_rand(x1, y0, z0, seed);
+    let c010 = lattice_rand(x0, y1, z0, seed);
+    let c110 = lattice_rand(x1, y1, z0, seed);
+    let c001 = lattice_rand(x0, y0, z1, seed);
+    let c101 = lattice_rand(x1, y0, z1, seed);
+    let c011 = lattice_rand(x0, y1, z1, seed);
+    let c111 = lattice_rand(x1, y1, z1, seed);
 
     let x00 = lerp(c000, c100, tx);
     let x10 = lerp(c010, c110, tx);
@@ -140,12 +241,12 @@ pub fn value_noise3(p: Vector3) -> f32 {
 }
 
 /// Fractal Brownian Motion (FBM) using value_noise3; returns ~[-1,1]
-pub fn fbm3(mut p: Vector3, octaves: u32, lacunarity: f32, gain: f32) -> f32 {
+pub fn fbm3(mut p: Vector3, octaves: u32, lacunarity: f32, gain: f32, seed: u32) -> f32 {
     let mut amp = 0.5;
     let mut sum = 0.0;
     let mut total_amp = 0.0;
     for _ in 0..octaves {
-        let n = value_noise3(p) * 2.0 - 1.0; // to [-1,1]
+        let n = value_noise3(p, seed) * 2.0 - 1.0; // to [-1,1]
         sum += n * amp;
         total_amp += amp;
         p.x *= lacunarity; p.y *= lacunarity; p.z *= lacunarity;
@@ -153,3 +254,236 @@ pub fn fbm3(mut p: Vector3, octaves: u32, lacunarity: f32, gain: f32) -> f32 {
     }
     if total_amp > 0.0 { sum / total_amp } else { 0.0 }
 }
+
+/// Stable per-body seed from the scene seed and a name (FNV-1a + mix).
+/// Same inputs give the same seed on every run and machine.
+pub fn derive_seed(scene_seed: u32, name: &str) -> u32 {
+    let mut h = 2166136261u32;
+    for b in name.bytes() {
+        h ^= b as u32;
+        h = h.wrapping_mul(16777619);
+    }
+    hash_u32(h ^ hash_u32(scene_seed))
+}
+
+// --- Noise bases: gradient (Perlin), simplex, Worley + fractal variants ---
+
+/// Which noise function an FBM-style sum is built on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NoiseBasis {
+    Value,                          // hashed value noise (blocky, cheapest)
+    Perlin,                         // gradient noise
+    Simplex,                        // simplex gradient noise (fewer axis artifacts)
+    Worley,                         // cellular, F1 distance
+    Ridged,                         // ridged multifractal over Perlin (mountain ridges)
+    Billow,                         // |Perlin| sum (puffy clouds / granulation)
+    DomainWarp { strength: f32 },   // Perlin FBM with its domain warped by another FBM
+}
+
+#[inline]
+pub fn lattice_hash(i: i32, j: i32, k: i32, seed: u32) -> u32 {
+    let mut h = 1469598103u32; // FNV-like mix, seeded so each body gets its own lattice
+    h ^= seed; h = h.wrapping_mul(16777619);
+    h ^= i as u32; h = h.wrapping_mul(16777619);
+    h ^= j as u32; h = h.wrapping_mul(16777619);
+    h ^= k as u32; h = h.wrapping_mul(16777619);
+    hash_u32(h)
+}
+
+// 12 cube-edge gradients (Perlin 2002)
+const GRAD3: [[f32; 3]; 12] = [
+    [1.0, 1.0, 0.0], [-1.0, 1.0, 0.0], [1.0, -1.0, 0.0], [-1.0, -1.0, 0.0],
+    [1.0, 0.0, 1.0], [-1.0, 0.0, 1.0], [1.0, 0.0, -1.0], [-1.0, 0.0, -1.0],
+    [0.0, 1.0, 1.0], [0.0, -1.0, 1.0], [0.0, 1.0, -1.0], [0.0, -1.0, -1.0],
+];
+
+#[inline]
+fn grad_dot(i: i32, j: i32, k: i32, x: f32, y: f32, z: f32, seed: u32) -> f32 {
+    let g = GRAD3[(lattice_hash(i, j, k, seed) % 12) as usize];
+    g[0] * x + g[1] * y + g[2] * z
+}
+
+#[inline]
+fn quintic(t: f32) -> f32 { t * t * t * (t * (t * 6.0 - 15.0) + 10.0) }
+
+/// 3D gradient (Perlin) noise, ~[-1,1]
+pub fn perlin_noise3(p: Vector3, seed: u32) -> f32 {
+    let x0 = p.x.floor() as i32;
+    let y0 = p.y.floor() as i32;
+    let z0 = p.z.floor() as i32;
+    let fx = p.x - x0 as f32;
+    let fy = p.y - y0 as f32;
+    let fz = p.z - z0 as f32;
+    let u = quintic(fx);
+    let v = quintic(fy);
+    let w = quintic(fz);
+
+    let n000 = grad_dot(x0,     y0,     z0,     fx,       fy,       fz, seed);
+    let n100 = grad_dot(x0 + 1, y0,     z0,     fx - 1.0, fy,       fz, seed);
+    let n010 = grad_dot(x0,     y0 + 1, z0,     fx,       fy - 1.0, fz, seed);
+    let n110 = grad_dot(x0 + 1, y0 + 1, z0,     fx - 1.0, fy - 1.0, fz, seed);
+    let n001 = grad_dot(x0,     y0,     z0 + 1, fx,       fy,       fz - 1.0, seed);
+    let n101 = grad_dot(x0 + 1, y0,     z0 + 1, fx - 1.0, fy,       fz - 1.0, seed);
+    let n011 = grad_dot(x0,     y0 + 1, z0 + 1, fx,       fy - 1.0, fz - 1.0, seed);
+    let n111 = grad_dot(x0 + 1, y0 + 1, z0 + 1, fx - 1.0, fy - 1.0, fz - 1.0, seed);
+
+    let x00 = lerp(n000, n100, u);
+    let x10 = lerp(n010, n110, u);
+    let x01 = lerp(n001, n101, u);
+    let x11 = lerp(n011, n111, u);
+    (lerp(lerp(x00, x10, v), lerp(x01, x11, v), w)).clamp(-1.0, 1.0)
+}
+
+/// 3D simplex noise (Gustavson), ~[-1,1]
+pub fn simplex_noise3(p: Vector3, seed: u32) -> f32 {
+    const F3: f32 = 1.0 / 3.0;
+    const G3: f32 = 1.0 / 6.0;
+
+    // Skew to the simplex grid and find the containing cell
+    let s = (p.x + p.y + p.z) * F3;
+    let i = (p.x + s).floor() as i32;
+    let j = (p.y + s).floor() as i32;
+    let k = (p.z + s).floor() as i32;
+    let t = (i + j + k) as f32 * G3;
+    let x0 = p.x - (i as f32 - t);
+    let y0 = p.y - (j as f32 - t);
+    let z0 = p.z - (k as f32 - t);
+
+    // Pick which of the six tetrahedra we are in
+    let (i1, j1, k1, i2, j2, k2) = if x0 >= y0 {
+        if y0 >= z0      { (1, 0, 0, 1, 1, 0) }
+        else if x0 >= z0 { (1, 0, 0, 1, 0, 1) }
+        else             { (0, 0, 1, 1, 0, 1) }
+    } else if y0 < z0    { (0, 0, 1, 0, 1, 1) }
+    else if x0 < z0      { (0, 1, 0, 0, 1, 1) }
+    else                 { (0, 1, 0, 1, 1, 0) };
+
+    let corners = [
+        (0, 0, 0, x0, y0, z0),
+        (i1, j1, k1, x0 - i1 as f32 + G3, y0 - j1 as f32 + G3, z0 - k1 as f32 + G3),
+        (i2, j2, k2, x0 - i2 as f32 + 2.0 * G3, y0 - j2 as f32 + 2.0 * G3, z0 - k2 as f32 + 2.0 * G3),
+        (1, 1, 1, x0 - 1.0 + 3.0 * G3, y0 - 1.0 + 3.0 * G3, z0 - 1.0 + 3.0 * G3),
+    ];
+
+    let mut n = 0.0;
+    for (di, dj, dk, x, y, z) in corners {
+        let t = 0.6 - x * x - y * y - z * z;
+        if t > 0.0 {
+            let t2 = t * t;
+            n += t2 * t2 * grad_dot(i + di, j + dj, k + dk, x, y, z, seed);
+        }
+    }
+    (32.0 * n).clamp(-1.0, 1.0)
+}
+
+/// 3D Worley (cellular) noise: distances to the nearest and second-nearest feature points
+pub fn worley_noise3(p: Vector3, seed: u32) -> (f32, f32) {
+    let cx = p.x.floor() as i32;
+    let cy = p.y.floor() as i32;
+    let cz = p.z.floor() as i32;
+    let mut f1 = f32::MAX;
+    let mut f2 = f32::MAX;
+    for dz in -1..=1 {
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let (i, j, k) = (cx + dx, cy + dy, cz + dz);
+                // One jittered feature point per cell
+                let h = lattice_hash(i, j, k, seed);
+                let hx = hash_u32(h);
+                let hy = hash_u32(hx);
+                let hz = hash_u32(hy);
+                let fp = Vector3::new(
+                    i as f32 + hx as f32 / u32::MAX as f32,
+                    j as f32 + hy as f32 / u32::MAX as f32,
+                    k as f32 + hz as f32 / u32::MAX as f32,
+                );
+                let d = (fp - p).length();
+                if d < f1 { f2 = f1; f1 = d; } else if d < f2 { f2 = d; }
+            }
+        }
+    }
+    (f1, f2)
+}
+
+/// Generic FBM over a chosen basis; returns ~[-1,1]
+fn fbm_with(mut p: Vector3, octaves: u32, lacunarity: f32, gain: f32, basis: impl Fn(Vector3) -> f32) -> f32 {
+    let mut amp = 0.5;
+    let mut sum = 0.0;
+    let mut total_amp = 0.0;
+    for _ in 0..octaves {
+        sum += basis(p) * amp;
+        total_amp += amp;
+        p = p * lacunarity;
+        amp *= gain;
+    }
+    if total_amp > 0.0 { sum / total_amp } else { 0.0 }
+}
+
+/// Ridged multifractal (Musgrave) over Perlin noise; returns ~[-1,1]
+pub fn ridged3(mut p: Vector3, octaves: u32, lacunarity: f32, gain: f32, seed: u32) -> f32 {
+    let offset = 1.0;
+    let mut weight = 1.0;
+    let mut amp = 0.5;
+    let mut sum = 0.0;
+    let mut total_amp = 0.0;
+    for _ in 0..octaves {
+        let mut signal = offset - perlin_noise3(p, seed).abs();
+        signal *= signal;
+        // Sharp ridges feed detail into the next octave; valleys stay smooth
+        signal *= weight;
+        weight = (signal * 2.0).clamp(0.0, 1.0);
+        sum += signal * amp;
+        total_amp += amp;
+        p = p * lacunarity;
+        amp *= gain;
+    }
+    if total_amp > 0.0 { (sum / total_amp) * 2.0 - 1.0 } else { 0.0 }
+}
+
+/// Billow noise (sum of |Perlin|); returns ~[-1,1]
+pub fn billow3(p: Vector3, octaves: u32, lacunarity: f32, gain: f32, seed: u32) -> f32 {
+    // |n| of Perlin sits mostly in [0,0.5], so stretch it before recentering
+    fbm_with(p, octaves, lacunarity, gain, |q| perlin_noise3(q, seed).abs() * 4.0 - 1.0).clamp(-1.0, 1.0)
+}
+
+/// Domain-warped Perlin FBM: f(p + strength * q(p)); returns ~[-1,1]
+pub fn warped_fbm3(p: Vector3, octaves: u32, lacunarity: f32, gain: f32, strength: f32, seed: u32) -> f32 {
+    let fbm = |q: Vector3| fbm_with(q, octaves, lacunarity, gain, |r| perlin_noise3(r, seed));
+    let q = Vector3::new(
+        fbm(p),
+        fbm(p + Vector3::new(5.2, 1.3, 2.8)),
+        fbm(p + Vector3::new(1.7, 9.2, 4.1)),
+    );
+    fbm(p + q * strength)
+}
+
+/// Curl of a Perlin vector potential (finite differences): a divergence-free flow field,
+/// handy for advecting other noise so it swirls instead of just sliding
+pub fn curl_noise3(p: Vector3, seed: u32) -> Vector3 {
+    const E: f32 = 0.01;
+    let psi = |q: Vector3, k: u32| perlin_noise3(q, seed ^ k.wrapping_mul(0x9E37_79B9));
+    let d = |k: u32, axis: Vector3| (psi(p + axis * E, k) - psi(p - axis * E, k)) / (2.0 * E);
+    let (ex, ey, ez) = (Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0), Vector3::new(0.0, 0.0, 1.0));
+    Vector3::new(
+        d(3, ey) - d(2, ez),
+        d(1, ez) - d(3, ex),
+        d(2, ex) - d(1, ey),
+    )
+}
+
+/// Most octaves a user file (expression or material graph) may ask for; past this they're
+/// finer than a pixel anyway, and each one costs a full noise evaluation per fragment
+pub const MAX_OCTAVES: u32 = 16;
+
+/// Fractal noise over any basis; returns ~[-1,1]. `Value` matches `fbm3`.
+pub fn noise3(basis: NoiseBasis, p: Vector3, octaves: u32, lacunarity: f32, gain: f32, seed: u32) -> f32 {
+    match basis {
+        NoiseBasis::Value => fbm3(p, octaves, lacunarity, gain, seed),
+        NoiseBasis::Perlin => fbm_with(p, octaves, lacunarity, gain, |q| perlin_noise3(q, seed)),
+        NoiseBasis::Simplex => fbm_with(p, octaves, lacunarity, gain, |q| simplex_noise3(q, seed)),
+        NoiseBasis::Worley => fbm_with(p, octaves, lacunarity, gain, |q| 1.0 - 2.0 * worley_noise3(q, seed).0.min(1.0)),
+        NoiseBasis::Ridged => ridged3(p, octaves, lacunarity, gain, seed),
+        NoiseBasis::Billow => billow3(p, octaves, lacunarity, gain, seed),
+        NoiseBasis::DomainWarp { strength } => warped_fbm3(p, octaves, lacunarity, gain, strength, seed),
+    }
+}
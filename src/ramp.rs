@@ -0,0 +1,182 @@
+// src/ramp.rs
+use raylib::prelude::*;
+
+/// How a ramp blends between two neighbouring stops
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RampMode {
+    Linear,     // straight RGB lerp
+    Smoothstep, // RGB lerp eased at each stop (flat shoulders, no kinks)
+    Oklab,      // lerp in OKLab: perceptually even, keeps hues from going muddy between stops
+}
+
+/// Gradient through arbitrary color stops, e.g. ocean -> sand -> grass -> rock -> snow.
+/// Positions are usually 0..1; `sample` clamps to the first and last stop.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColorRamp {
+    pub mode: RampMode,
+    stops: Vec<(f32, Vector3)>, // sorted by position
+    lab: Vec<Vector3>,          // stop colors in OKLab, for RampMode::Oklab
+}
+
+impl ColorRamp {
+    pub fn new(mode: RampMode, stops: &[(f32, Vector3)]) -> Self {
+        let mut stops = stops.to_vec();
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let lab = stops.iter().map(|&(_, c)| rgb_to_oklab(c)).collect();
+        ColorRamp { mode, stops, lab }
+    }
+
+    /// Ocean -> sand -> grass -> rock -> snow, the colors of `TerrainParams::earth`, in OKLab
+    pub fn terrain() -> Self {
+        Self::new(RampMode::Oklab, &[
+            (0.0, Vector3::new(0.02, 0.07, 0.22)),
+            (0.45, Vector3::new(0.76, 0.70, 0.50)),
+            (0.55, Vector3::new(0.25, 0.45, 0.18)),
+            (0.8, Vector3::new(0.42, 0.36, 0.31)),
+            (1.0, Vector3::new(0.95, 0.96, 0.98)),
+        ])
+    }
+
+    /// `pos:r,g,b` stops separated by `;` or whitespace, e.g. "0:0.02,0.07,0.22; 0.5:0.76,0.7,0.5; 1:1"
+    /// (a single number is a gray)
+    pub fn parse(mode: RampMode, src: &str) -> Result<Self, String> {
+        let mut stops = Vec::new();
+        for stop in src.split(|c: char| c == ';' || c.is_whitespace()).filter(|s| !s.is_empty()) {
+            let (pos, color) = stop.split_once(':').ok_or_else(|| format!("ramp stop `{}` is not pos:r,g,b", stop))?;
+            let pos: f32 = pos.parse().map_err(|_| format!("ramp stop `{}` has a bad position", stop))?;
+            let parts: Vec<f32> = color.split(',').map(|x| x.parse().ok()).collect::<Option<_>>()
+                .ok_or_else(|| format!("ramp stop `{}` has a bad color", stop))?;
+            let color = match parts[..] {
+                [v] => Vector3::new(v, v, v),
+                [r, g, b] => Vector3::new(r, g, b),
+                _ => return Err(format!("ramp stop `{}` needs 1 or 3 color components", stop)),
+            };
+            stops.push((pos, color));
+        }
+        if stops.is_empty() {
+            return Err("ramp has no stops".into());
+        }
+        Ok(Self::new(mode, &stops))
+    }
+
+    pub fn mode_named(name: &str) -> Option<RampMode> {
+        match name {
+            "linear" => Some(RampMode::Linear),
+            "smooth" | "smoothstep" => Some(RampMode::Smoothstep),
+            "oklab" => Some(RampMode::Oklab),
+            _ => None,
+        }
+    }
+
+    pub fn sample(&self, t: f32) -> Vector3 {
+        let n = self.stops.len();
+        if n == 0 {
+            return Vector3::zero();
+        }
+        if t <= self.stops[0].0 {
+            return self.stops[0].1;
+        }
+        if t >= self.stops[n - 1].0 {
+            return self.stops[n - 1].1;
+        }
+        // First stop past t; the span [i-1, i] contains it
+        let i = self.stops.partition_point(|s| s.0 <= t).clamp(1, n - 1);
+        let ((p0, c0), (p1, c1)) = (self.stops[i - 1], self.stops[i]);
+        let f = if p1 > p0 { (t - p0) / (p1 - p0) } else { 1.0 };
+        match self.mode {
+            RampMode::Linear => c0 + (c1 - c0) * f,
+            RampMode::Smoothstep => c0 + (c1 - c0) * (f * f * (3.0 - 2.0 * f)),
+            RampMode::Oklab => oklab_to_rgb(self.lab[i - 1] + (self.lab[i] - self.lab[i - 1]) * f),
+        }
+    }
+}
+
+// OKLab (Björn Ottosson, 2020) from linear sRGB; the pipeline's colors are treated as linear
+fn rgb_to_oklab(c: Vector3) -> Vector3 {
+    let l = 0.4122214708 * c.x + 0.5363325363 * c.y + 0.0514459929 * c.z;
+    let m = 0.2119034982 * c.x + 0.6806995451 * c.y + 0.1073969566 * c.z;
+    let s = 0.0883024619 * c.x + 0.2817188376 * c.y + 0.6299787005 * c.z;
+    let (l, m, s) = (l.max(0.0).cbrt(), m.max(0.0).cbrt(), s.max(0.0).cbrt());
+    Vector3::new(
+        0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+        1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+        0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+    )
+}
+
+fn oklab_to_rgb(lab: Vector3) -> Vector3 {
+    let l = lab.x + 0.3963377774 * lab.y + 0.2158037573 * lab.z;
+    let m = lab.x - 0.1055613458 * lab.y - 0.0638541728 * lab.z;
+    let s = lab.x - 0.0894841775 * lab.y - 1.2914855480 * lab.z;
+    let (l, m, s) = (l * l * l, m * m * m, s * s * s);
+    Vector3::new(
+        4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s,
+        -1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s,
+        -0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Vector3, b: Vector3) -> bool {
+        (a - b).length() < 1e-4
+    }
+
+    #[test]
+    fn parse_sorts_stops_and_reads_grays() {
+        let ramp = ColorRamp::parse(RampMode::Linear, "1:1,0,0; 0:0.5\n0.5:0,1,0").unwrap();
+        assert_eq!(ramp.stops, vec![
+            (0.0, Vector3::new(0.5, 0.5, 0.5)),
+            (0.5, Vector3::new(0.0, 1.0, 0.0)),
+            (1.0, Vector3::new(1.0, 0.0, 0.0)),
+        ]);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let cases = [
+            ("0.5", "ramp stop `0.5` is not pos:r,g,b"),
+            ("0:0; x:1", "ramp stop `x:1` has a bad position"),
+            ("0:1,a,0", "ramp stop `0:1,a,0` has a bad color"),
+            ("0:1,0", "ramp stop `0:1,0` needs 1 or 3 color components"),
+            (" ; ", "ramp has no stops"),
+        ];
+        for (src, expected) in cases {
+            assert_eq!(ColorRamp::parse(RampMode::Linear, src).unwrap_err(), expected);
+        }
+        assert_eq!(ColorRamp::mode_named("smooth"), Some(RampMode::Smoothstep));
+        assert_eq!(ColorRamp::mode_named("cubic"), None);
+    }
+
+    #[test]
+    fn endpoints_and_midpoints_in_every_mode() {
+        let (black, white) = (Vector3::zero(), Vector3::one());
+        // OKLab lightness is the cube root of luminance, so its midpoint gray is 0.5^3
+        for (mode, quarter, mid) in [
+            (RampMode::Linear, 0.25, 0.5),
+            (RampMode::Smoothstep, 0.15625, 0.5),
+            (RampMode::Oklab, 0.25 * 0.25 * 0.25, 0.125),
+        ] {
+            let ramp = ColorRamp::new(mode, &[(0.0, black), (1.0, white)]);
+            assert!(close(ramp.sample(0.0), black) && close(ramp.sample(1.0), white), "{:?}", mode);
+            assert!(close(ramp.sample(-3.0), black) && close(ramp.sample(3.0), white), "{:?}", mode);
+            assert!(close(ramp.sample(0.25), Vector3::one() * quarter), "{:?}: {:?}", mode, ramp.sample(0.25));
+            assert!(close(ramp.sample(0.5), Vector3::one() * mid), "{:?}: {:?}", mode, ramp.sample(0.5));
+        }
+    }
+
+    #[test]
+    fn oklab_round_trip() {
+        assert!(close(rgb_to_oklab(Vector3::one()), Vector3::new(1.0, 0.0, 0.0)));
+        for c in [Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.2, 0.7, 0.4), Vector3::new(0.02, 0.07, 0.22)] {
+            assert!(close(oklab_to_rgb(rgb_to_oklab(c)), c), "{:?}", c);
+        }
+        // Stops come back exactly at their own positions
+        let terrain = ColorRamp::terrain();
+        for &(pos, color) in &terrain.stops {
+            assert!(close(terrain.sample(pos), color), "{}", pos);
+        }
+    }
+}
@@ -0,0 +1,131 @@
+// src/rings.rs
+use raylib::prelude::*;
+
+use crate::procedural::{mix, noise3, smoothstep, NoiseBasis};
+
+/// Planetary ring system shaded on the flat annulus from `generate_ring` (object space XZ plane,
+/// centered on the parent planet). Everything is a function of the radial distance.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RingParams {
+    pub inner_radius: f32,           // must match the generate_ring radii
+    pub outer_radius: f32,
+    pub planet_radius: f32,          // parent planet, for the shadow it casts on the rings
+
+    // Normal optical depth by fractional radius u = (r - inner) / (outer - inner):
+    // keyframes (u, tau) interpolated smoothly, then gaps carved out as (center u, width u)
+    pub profile: Vec<(f32, f32)>,
+    pub gaps: Vec<(f32, f32)>,
+    pub ringlet_freq: f32,           // fine radial structure (ringlets per ring width)
+    pub ringlet_amount: f32,
+
+    pub inner_color: Vector3,
+    pub outer_color: Vector3,
+    pub color_noise: f32,            // radial tint variation
+
+    pub forward_scatter: f32,        // Henyey-Greenstein g of the ring particles (0..1, dusty ~0.7)
+    pub back_light: f32,             // brightness of the unlit face when seen against the sun
+}
+
+impl RingParams {
+    /// Saturn-like: faint C ring, dense B ring, Cassini division, A ring with the Encke gap
+    pub fn saturnian(inner_radius: f32, outer_radius: f32, planet_radius: f32) -> Self {
+        Self {
+            inner_radius,
+            outer_radius,
+            planet_radius,
+            profile: vec![
+                (0.00, 0.05), (0.22, 0.12),              // C ring
+                (0.27, 1.20), (0.45, 2.00), (0.60, 1.60), // B ring
+                (0.64, 0.04), (0.68, 0.04),              // Cassini division
+                (0.71, 0.60), (0.95, 0.45),              // A ring
+                (1.00, 0.0),
+            ],
+            gaps: vec![(0.66, 0.05), (0.905, 0.012), (0.24, 0.008)],
+            ringlet_freq: 140.0,
+            ringlet_amount: 0.35,
+            inner_color: Vector3::new(0.55, 0.50, 0.44),
+            outer_color: Vector3::new(0.86, 0.80, 0.70),
+            color_noise: 0.08,
+            forward_scatter: 0.65,
+            back_light: 0.8,
+        }
+    }
+}
+
+/// Normal optical depth at object-space point `p` on the ring plane
+fn optical_depth(p: Vector3, r: &RingParams, seed: u32) -> f32 {
+    let radius = (p.x * p.x + p.z * p.z).sqrt();
+    let u = (radius - r.inner_radius) / (r.outer_radius - r.inner_radius);
+    if !(0.0..=1.0).contains(&u) {
+        return 0.0;
+    }
+
+    // Piecewise smooth interpolation of the keyframes
+    let mut tau = r.profile.last().map_or(0.0, |k| k.1);
+    for w in r.profile.windows(2) {
+        let ((u0, t0), (u1, t1)) = (w[0], w[1]);
+        if u >= u0 && u <= u1 {
+            tau = t0 + (t1 - t0) * smoothstep(u0, u1, u);
+            break;
+        }
+    }
+
+    for &(center, width) in &r.gaps {
+        tau *= smoothstep(width * 0.35, width * 0.5, (u - center).abs());
+    }
+
+    // Ringlets: 1D noise along the radius only
+    let n = noise3(NoiseBasis::Perlin, Vector3::new(u * r.ringlet_freq, 0.0, 0.0), 3, 2.0, 0.5, seed) * 0.5 + 0.5;
+    tau * (1.0 - r.ringlet_amount + 2.0 * r.ringlet_amount * n)
+}
+
+/// Opacity seen along a view ray: slant path through a layer of normal optical depth tau
+pub fn ring_alpha(p: Vector3, normal: Vector3, view: Vector3, r: &RingParams, seed: u32) -> f32 {
+    let mu = normal.normalized().dot(view).abs().max(0.02);
+    1.0 - (-optical_depth(p, r, seed) / mu).exp()
+}
+
+/// Ring color at object-space `p` under a white star (multiply by the light color). `normal`,
+/// `view` (to the eye) and `light` (to the star) are world-space unit vectors; `light_obj` is the
+/// direction to the star in object space.
+pub fn ring_color(p: Vector3, normal: Vector3, view: Vector3, light: Vector3, light_obj: Vector3, r: &RingParams, seed: u32) -> Vector3 {
+    let radius = (p.x * p.x + p.z * p.z).sqrt();
+    let u = ((radius - r.inner_radius) / (r.outer_radius - r.inner_radius)).clamp(0.0, 1.0);
+    let tint = noise3(NoiseBasis::Perlin, Vector3::new(u * 23.0, 0.0, 7.0), 2, 2.0, 0.5, seed ^ 0x51A6_0001);
+    let albedo = mix(r.inner_color, r.outer_color, u) * (1.0 + r.color_noise * tint);
+
+    let tau = optical_depth(p, r, seed);
+    let n = normal.normalized();
+    let (mu_v, mu_l) = (n.dot(view), n.dot(light));
+
+    let brightness = if mu_v * mu_l >= 0.0 {
+        // Lit face: single scattering off a slab, brighter where the ring is dense
+        let mu_l = mu_l.abs().max(0.02);
+        (1.0 - (-tau / mu_l).exp()) * 0.9 + 0.1 * mu_l
+    } else {
+        // Unlit face seen against the sun: light diffusing through, strongest for thin regions
+        // and when looking toward the star (forward scattering)
+        let cos_theta = (-view).dot(light);
+        let through = tau * (-tau / mu_l.abs().max(0.02)).exp();
+        through * henyey_greenstein(cos_theta, r.forward_scatter) * r.back_light
+    };
+
+    albedo * (brightness * planet_shadow(p, light_obj, r))
+}
+
+/// 1 where the star is visible from the ring point, 0 inside the parent planet's shadow
+fn planet_shadow(p: Vector3, light_obj: Vector3, r: &RingParams) -> f32 {
+    let l = light_obj.normalized();
+    let along = p.dot(l);
+    if along >= 0.0 {
+        return 1.0; // point is on the star's side of the planet
+    }
+    let miss = (p - l * along).length(); // distance from the planet center to the ray toward the star
+    smoothstep(r.planet_radius * 0.97, r.planet_radius * 1.03, miss)
+}
+
+/// Henyey-Greenstein phase function normalized so that isotropic scattering is 1
+fn henyey_greenstein(cos_theta: f32, g: f32) -> f32 {
+    let g2 = g * g;
+    (1.0 - g2) / (1.0 + g2 - 2.0 * g * cos_theta).max(1e-4).powf(1.5)
+}
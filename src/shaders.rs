@@ -2,33 +2,98 @@ use std::f32::consts::PI;
 use raylib::math::*;
 use raylib::prelude::*;
 
+use crate::blackbody::blackbody_rgb;
 use crate::fragment::Fragment;
 use crate::uniforms::{Uniforms, color_to_vec3};
-use crate::procedural::fbm3;
+use crate::procedural::{noise3, NoiseBasis};
+use crate::craters::{crater_field, CraterParams};
+use crate::terrain::{terrain_height, terrain_sample, TerrainParams, TerrainSample};
+use crate::gas_giant::{gas_giant_color, GasGiantParams};
+use crate::star::{corona_glow, star_surface, CoronaParams, StarParams};
+use crate::clouds::{cloud_alpha, cloud_shadow, CloudParams};
+use crate::cities::{city_lights, CityLightParams};
+use crate::rings::{ring_alpha, ring_color, RingParams};
+use crate::bump::{bumped_normal, normal_mapped, BumpParams, NormalMap};
+use crate::texture::{equirect_uv, TextureMap, UvMapping};
+use crate::ramp::ColorRamp;
+use crate::layers::{composite, BandsLayer, Layer, LayerKind, NoiseLayer};
+use crate::expr::ExprInputs;
+use crate::graph::GraphInputs;
+use crate::matrix::multiply_matrix_vector4;
 
 // -------------------- Material (per-entity) --------------------
+/// How a material's fragments reach the framebuffer
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BlendMode {
+    Opaque,   // depth-tested, writes depth
+    Alpha,    // blends over what is behind it by `fragment_alpha`, no depth write
+    Additive, // adds onto what is behind it, no depth write; drawn after opaque bodies and the sky
+}
+
+/// Where the lighting stage gets its normal
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ShadingMode {
+    Flat,    // one face normal per triangle: the faceted look of low-poly meshes
+    Gouraud, // lit at the vertices and interpolated; cheapest, but no per-pixel relief or sharp highlights
+    Phong,   // interpolated normals (plus bump/normal maps), lit per fragment
+}
+
 #[derive(Clone)]
 pub struct Material {
-    // L1: two cosine palettes (IQ-style) to blend
+    pub blend: BlendMode,
+    pub shading: ShadingMode,
+    // Surface layer: two cosine palettes (IQ-style) to blend
     pub pal1_a: Vector3, pub pal1_b: Vector3, pub pal1_c: Vector3, pub pal1_d: Vector3,
     pub pal2_a: Vector3, pub pal2_b: Vector3, pub pal2_c: Vector3, pub pal2_d: Vector3,
+    pub pal1_ramp: Option<ColorRamp>, pub pal2_ramp: Option<ColorRamp>, // if set, used instead of that cosine palette
     pub pal_mix_radius: f32, // how quickly we mix pal1->pal2 with radius (0..1 region around center)
+    pub craters: Option<CraterParams>, // crater field shaded in object space (match the vertex shader)
+    pub crater_floor_dark: f32, pub crater_ray_bright: f32,
+    pub terrain: Option<TerrainParams>, // if set, the Surface albedo comes from the terrain classifier instead of the palettes
+    pub gas: Option<GasGiantParams>,    // if set, the Surface albedo comes from the gas giant band/storm model
+    pub star: Option<StarParams>,       // if set, Surface is a self-lit photosphere (limb darkening, granulation, spots)
+    pub corona: Option<CoronaParams>,   // if set, Surface is a corona glow; use with BlendMode::Additive on a shell mesh
+    pub clouds: Option<CloudParams>,    // if set, this is a cloud shell (BlendMode::Alpha, coverage from `fragment_alpha`)
+    pub cloud_shadow: Option<CloudParams>, // clouds above this surface, darkening the ground they cover
+    pub city_lights: Option<CityLightParams>, // emissive night-side lights (on terrain land if `terrain` is set)
+    pub rings: Option<RingParams>,      // if set, Surface is a lit ring system by radius (BlendMode::Alpha on a generate_ring mesh)
+    pub bump: Option<BumpParams>,       // per-fragment relief from the gradient of a procedural height
+    pub normal_map: Option<NormalMap>,  // tangent-space normal map (meshes with UVs only)
+    pub albedo_map: Option<TextureMap>,   // image blended over the Surface albedo by `strength`
+    pub emission_map: Option<TextureMap>, // self-lit color (night side included), scaled by `strength`
+    pub specular_map: Option<TextureMap>, // red channel = highlight weight (e.g. oceans), scaled by `strength`
+    pub height_map: Option<TextureMap>,   // grayscale relief for the bump normal (Equirect mapping only)
+    // Cook-Torrance/GGX response to the star's light, applied after the procedural albedo
+    pub roughness: f32, // 0 = mirror-like, 1 = fully matte
+    pub metallic: f32,  // 0 = dielectric, 1 = metal (highlights tinted by the albedo, no diffuse)
+    pub specular: f32,  // dielectric reflectance at normal incidence, 0..1 -> F0 0..0.08 (0.5 = 4%, most rocks)
+    pub emissive: f32,  // share of the albedo the surface emits itself; 1 = self-lit (stars), ignores the light
 
-    // L2: stripes/rings/checker and accent
-    pub rings_freq: f32, pub rings_speed: f32,
-    pub stripes_angle_deg: f32, pub stripes_freq: f32, pub stripes_speed: f32,
-    pub checker_scale: f32,
-    pub rings_weight: f32, pub stripes_weight: f32, pub checker_weight: f32,
-    pub accent: Vector3, pub accent_strength: f32,
-
-    // L3: noise/perturb
-    pub fbm_freq: f32, pub fbm_octaves: u32, pub fbm_lacunarity: f32, pub fbm_gain: f32, pub fbm_time: f32,
-    pub sat_base: f32, pub sat_amp: f32,
-    pub bri_base: f32, pub bri_amp: f32,
+    // Ordered stack composited over the base color (see `layers.rs`); `Surface` draws everything above
+    pub layers: Vec<Layer>,
+    pub seed: u32, // feeds the noise hash; set per entity from the scene seed
+}
 
-    // L4: scanlines/bloom
-    pub scan_intensity: f32,
-    pub bloom_strength: f32,
+/// Neutral base the presets override: opaque, Phong-shaded, flat grey palettes, a bare
+/// Surface layer and none of the optional models or maps
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            blend: BlendMode::Opaque,
+            shading: ShadingMode::Phong,
+            pal1_a: Vector3::new(0.5, 0.5, 0.5), pal1_b: Vector3::zero(), pal1_c: Vector3::one(), pal1_d: Vector3::zero(),
+            pal2_a: Vector3::new(0.5, 0.5, 0.5), pal2_b: Vector3::zero(), pal2_c: Vector3::one(), pal2_d: Vector3::zero(),
+            pal1_ramp: None, pal2_ramp: None,
+            pal_mix_radius: 0.6,
+            craters: None, crater_floor_dark: 0.0, crater_ray_bright: 0.0,
+            terrain: None, gas: None, star: None, corona: None, clouds: None, cloud_shadow: None, city_lights: None, rings: None,
+            bump: None, normal_map: None,
+            albedo_map: None, emission_map: None, specular_map: None, height_map: None,
+            roughness: 0.8, metallic: 0.0, specular: 0.5, emissive: 0.0,
+            layers: vec![Layer::surface()],
+            seed: 0,
+        }
+    }
 }
 
 impl Material {
@@ -37,15 +102,27 @@ impl Material {
             pal1_a: Vector3::new(0.55, 0.45, 0.40), pal1_b: Vector3::new(0.30, 0.25, 0.20), pal1_c: Vector3::new(1.0, 1.0, 1.0), pal1_d: Vector3::new(0.00, 0.15, 0.20),
             pal2_a: Vector3::new(0.45, 0.35, 0.30), pal2_b: Vector3::new(0.25, 0.20, 0.15), pal2_c: Vector3::new(1.0, 1.0, 1.0), pal2_d: Vector3::new(0.35, 0.10, 0.20),
             pal_mix_radius: 0.6,
-            rings_freq: 12.0, rings_speed: 0.2,
-            stripes_angle_deg: 25.0, stripes_freq: 18.0, stripes_speed: 0.2,
-            checker_scale: 6.0,
-            rings_weight: 0.15, stripes_weight: 0.10, checker_weight: 0.05,
-            accent: Vector3::new(0.20, 0.16, 0.12), accent_strength: 0.25,
-            fbm_freq: 5.0, fbm_octaves: 5, fbm_lacunarity: 2.0, fbm_gain: 0.5, fbm_time: 0.25,
-            sat_base: 0.7, sat_amp: 0.35,
-            bri_base: 0.9, bri_amp: 0.25,
-            scan_intensity: 0.06, bloom_strength: 0.08,
+            bump: Some(BumpParams { strength: 0.5, ..BumpParams::noise(0.08, 2.5, 7, NoiseBasis::Ridged) }),
+            roughness: 0.85, metallic: 0.0, specular: 0.3, emissive: 0.0,
+            layers: vec![
+                Layer::surface(),
+                Layer::bands(BandsLayer {
+                    rings_freq: 12.0, rings_speed: 0.2,
+                    stripes_angle_deg: 25.0, stripes_freq: 18.0, stripes_speed: 0.2,
+                    checker_scale: 6.0,
+                    rings_weight: 0.15, stripes_weight: 0.10, checker_weight: 0.05,
+                    accent: Vector3::new(0.20, 0.16, 0.12), accent_strength: 0.25,
+                }),
+                Layer::noise(NoiseLayer {
+                    freq: 5.0, octaves: 5, lacunarity: 2.0, gain: 0.5, time: 0.25,
+                    basis: NoiseBasis::Ridged,
+                    sat_base: 0.7, sat_amp: 0.35,
+                    bri_base: 0.9, bri_amp: 0.25,
+                }),
+                Layer::scanlines(0.06),
+                Layer::bloom(0.08),
+            ],
+            ..Self::default()
         }
     }
     pub fn gaseous() -> Self {
@@ -53,47 +130,77 @@ impl Material {
             pal1_a: Vector3::new(0.15, 0.35, 0.60), pal1_b: Vector3::new(0.20, 0.35, 0.40), pal1_c: Vector3::new(1.0, 1.0, 1.0), pal1_d: Vector3::new(0.05, 0.10, 0.20),
             pal2_a: Vector3::new(0.75, 0.60, 0.30), pal2_b: Vector3::new(0.30, 0.30, 0.20), pal2_c: Vector3::new(1.0, 1.0, 1.0), pal2_d: Vector3::new(0.20, 0.15, 0.10),
             pal_mix_radius: 0.9,
-            rings_freq: 24.0, rings_speed: 0.5,
-            stripes_angle_deg: 12.0, stripes_freq: 48.0, stripes_speed: 0.9,
-            checker_scale: 10.0,
-            rings_weight: 0.45, stripes_weight: 0.45, checker_weight: 0.10,
-            accent: Vector3::new(0.08, 0.65, 0.95), accent_strength: 0.35,
-            fbm_freq: 3.5, fbm_octaves: 4, fbm_lacunarity: 2.0, fbm_gain: 0.55, fbm_time: 0.35,
-            sat_base: 0.9, sat_amp: 0.20,
-            bri_base: 0.95, bri_amp: 0.30,
-            scan_intensity: 0.05, bloom_strength: 0.12,
+            gas: Some(GasGiantParams::jovian()),
+            roughness: 0.6, metallic: 0.0, specular: 0.4, emissive: 0.0,
+            layers: vec![
+                Layer::surface(),
+                Layer::noise(NoiseLayer {
+                    freq: 3.5, octaves: 4, lacunarity: 2.0, gain: 0.55, time: 0.35,
+                    basis: NoiseBasis::DomainWarp { strength: 1.2 },
+                    sat_base: 0.95, sat_amp: 0.10,
+                    bri_base: 0.95, bri_amp: 0.12,
+                }),
+                Layer::scanlines(0.05),
+                Layer::bloom(0.12),
+            ],
+            ..Self::default()
         }
     }
+    /// Saturn-like rings for the scene's `generate_ring(1.6, 2.4, ..)` around a 1.2 planet
     pub fn ring() -> Self {
+        Self::rings(RingParams::saturnian(1.6, 2.4, 1.2))
+    }
+    /// Translucent ring system shaded by radius; `params` radii must match the ring mesh
+    pub fn rings(params: RingParams) -> Self {
         Self {
+            blend: BlendMode::Alpha,
             pal1_a: Vector3::new(0.65, 0.60, 0.55), pal1_b: Vector3::new(0.20, 0.20, 0.20), pal1_c: Vector3::new(1.0, 1.0, 1.0), pal1_d: Vector3::new(0.10, 0.10, 0.10),
             pal2_a: Vector3::new(0.55, 0.50, 0.45), pal2_b: Vector3::new(0.15, 0.15, 0.15), pal2_c: Vector3::new(1.0, 1.0, 1.0), pal2_d: Vector3::new(0.20, 0.20, 0.20),
             pal_mix_radius: 0.8,
-            rings_freq: 90.0, rings_speed: 0.0,
-            stripes_angle_deg: 0.0, stripes_freq: 0.0, stripes_speed: 0.0,
-            checker_scale: 14.0,
-            rings_weight: 0.85, stripes_weight: 0.0, checker_weight: 0.05,
-            accent: Vector3::new(0.9, 0.85, 0.7), accent_strength: 0.12,
-            fbm_freq: 8.0, fbm_octaves: 3, fbm_lacunarity: 2.0, fbm_gain: 0.5, fbm_time: 0.0,
-            sat_base: 0.85, sat_amp: 0.10,
-            bri_base: 0.95, bri_amp: 0.10,
-            scan_intensity: 0.04, bloom_strength: 0.08,
+            rings: Some(params),
+            roughness: 0.9, metallic: 0.0, specular: 0.2, emissive: 0.0,
+            layers: vec![Layer::surface(), Layer::bloom(0.05)],
+            ..Self::default()
         }
     }
+    /// Sun-like star (G2V, 5772 K)
     pub fn star() -> Self {
+        Self::star_at(5772.0)
+    }
+    /// Star defined by its effective temperature in Kelvin (~3000 red dwarf .. ~30000 blue giant).
+    /// Palettes and photosphere both come from the blackbody color of that temperature.
+    pub fn star_at(kelvin: f32) -> Self {
+        let hot = blackbody_rgb(kelvin);
+        let cool = blackbody_rgb(kelvin * 0.8); // toward the limb
         Self {
-            pal1_a: Vector3::new(1.0, 0.95, 0.85), pal1_b: Vector3::new(0.1, 0.1, 0.1), pal1_c: Vector3::new(1.0, 1.0, 1.0), pal1_d: Vector3::new(0.0, 0.33, 0.67),
-            pal2_a: Vector3::new(1.0, 0.9, 0.7),  pal2_b: Vector3::new(0.2, 0.1, 0.0), pal2_c: Vector3::new(1.0, 1.0, 1.0), pal2_d: Vector3::new(0.15, 0.33, 0.5),
+            pal1_a: hot, pal1_b: Vector3::new(0.1, 0.1, 0.1), pal1_c: Vector3::new(1.0, 1.0, 1.0), pal1_d: Vector3::new(0.0, 0.33, 0.67),
+            pal2_a: cool, pal2_b: Vector3::new(0.2, 0.1, 0.0), pal2_c: Vector3::new(1.0, 1.0, 1.0), pal2_d: Vector3::new(0.15, 0.33, 0.5),
             pal_mix_radius: 1.0,
-            rings_freq: 0.0, rings_speed: 0.0,
-            stripes_angle_deg: 0.0, stripes_freq: 0.0, stripes_speed: 0.0,
-            checker_scale: 0.0,
-            rings_weight: 0.0, stripes_weight: 0.0, checker_weight: 0.0,
-            accent: Vector3::new(1.0, 0.95, 0.85), accent_strength: 0.05,
-            fbm_freq: 1.8, fbm_octaves: 5, fbm_lacunarity: 2.0, fbm_gain: 0.54, fbm_time: 0.8,
-            sat_base: 1.0, sat_amp: 0.05,
-            bri_base: 1.1, bri_amp: 0.35,
-            scan_intensity: 0.03, bloom_strength: 0.35,
+            star: Some(StarParams { temperature: kelvin, ..StarParams::sun() }),
+            roughness: 1.0, metallic: 0.0, specular: 0.0, emissive: 1.0,
+            layers: vec![
+                Layer::surface(),
+                Layer::noise(NoiseLayer {
+                    freq: 1.8, octaves: 5, lacunarity: 2.0, gain: 0.54, time: 0.8,
+                    basis: NoiseBasis::Billow,
+                    sat_base: 1.0, sat_amp: 0.0,
+                    bri_base: 0.97, bri_amp: 0.06,
+                }),
+                Layer::bloom(0.2),
+            ],
+            ..Self::default()
+        }
+    }
+    /// Additive glow for a shell around a star (see `CoronaParams::shell_scale`)
+    pub fn corona() -> Self {
+        Self::corona_at(5772.0)
+    }
+    pub fn corona_at(kelvin: f32) -> Self {
+        Self {
+            blend: BlendMode::Additive,
+            star: None, corona: Some(CoronaParams { temperature: kelvin, ..CoronaParams::sun() }),
+            layers: vec![Layer::surface()],
+            ..Self::star_at(kelvin)
         }
     }
     pub fn moon() -> Self {
@@ -101,15 +208,70 @@ impl Material {
             pal1_a: Vector3::new(0.6, 0.6, 0.6), pal1_b: Vector3::new(0.2, 0.2, 0.2), pal1_c: Vector3::new(1.0, 1.0, 1.0), pal1_d: Vector3::new(0.2, 0.2, 0.2),
             pal2_a: Vector3::new(0.45, 0.45, 0.45), pal2_b: Vector3::new(0.15, 0.15, 0.15), pal2_c: Vector3::new(1.0, 1.0, 1.0), pal2_d: Vector3::new(0.3, 0.3, 0.3),
             pal_mix_radius: 0.6,
-            rings_freq: 0.0, rings_speed: 0.0,
-            stripes_angle_deg: 0.0, stripes_freq: 0.0, stripes_speed: 0.0,
-            checker_scale: 8.0,
-            rings_weight: 0.0, stripes_weight: 0.0, checker_weight: 0.05,
-            accent: Vector3::new(0.35, 0.35, 0.35), accent_strength: 0.15,
-            fbm_freq: 6.0, fbm_octaves: 5, fbm_lacunarity: 2.0, fbm_gain: 0.5, fbm_time: 0.25,
-            sat_base: 0.5, sat_amp: 0.2,
-            bri_base: 0.9, bri_amp: 0.15,
-            scan_intensity: 0.02, bloom_strength: 0.05,
+            craters: Some(CraterParams::lunar()), crater_floor_dark: 0.35, crater_ray_bright: 0.45,
+            bump: Some(BumpParams { craters: true, ..BumpParams::noise(0.012, 3.0, 6, NoiseBasis::Perlin) }),
+            roughness: 0.95, metallic: 0.0, specular: 0.2, emissive: 0.0,
+            layers: vec![
+                Layer::surface(),
+                Layer::bands(BandsLayer {
+                    rings_freq: 0.0, rings_speed: 0.0,
+                    stripes_angle_deg: 0.0, stripes_freq: 0.0, stripes_speed: 0.0,
+                    checker_scale: 8.0,
+                    rings_weight: 0.0, stripes_weight: 0.0, checker_weight: 0.05,
+                    accent: Vector3::new(0.35, 0.35, 0.35), accent_strength: 0.15,
+                }),
+                Layer::noise(NoiseLayer {
+                    freq: 6.0, octaves: 5, lacunarity: 2.0, gain: 0.5, time: 0.25,
+                    basis: NoiseBasis::Worley,
+                    sat_base: 0.5, sat_amp: 0.2,
+                    bri_base: 0.9, bri_amp: 0.15,
+                }),
+                Layer::scanlines(0.02),
+                Layer::bloom(0.05),
+            ],
+            ..Self::default()
+        }
+    }
+    /// Semi-transparent cloud shell; give the planet `cloud_shadow: Some(params)` with the same params
+    pub fn clouds(params: CloudParams) -> Self {
+        Self {
+            blend: BlendMode::Alpha,
+            clouds: Some(params), bump: None,
+            layers: vec![Layer::surface()],
+            ..Self::rocky()
+        }
+    }
+    pub fn terrain() -> Self {
+        Self {
+            terrain: Some(TerrainParams::earth()),
+            bump: Some(BumpParams { terrain_amp: 0.04, ..BumpParams::noise(0.0, 1.8, 1, NoiseBasis::Perlin) }),
+            layers: vec![
+                Layer::surface(),
+                Layer::noise(NoiseLayer {
+                    freq: 14.0, octaves: 3, lacunarity: 2.0, gain: 0.5, time: 0.25,
+                    basis: NoiseBasis::Perlin,
+                    sat_base: 0.95, sat_amp: 0.10,
+                    bri_base: 0.95, bri_amp: 0.10,
+                }),
+                Layer::scanlines(0.02),
+                Layer::bloom(0.04),
+            ],
+            ..Self::rocky()
+        }
+    }
+
+    /// Palette 1 at `t`: the ramp if set, else the cosine palette
+    pub fn palette1(&self, t: f32) -> Vector3 {
+        match &self.pal1_ramp {
+            Some(ramp) => ramp.sample(t),
+            None => palette_cosine(t, self.pal1_a, self.pal1_b, self.pal1_c, self.pal1_d),
+        }
+    }
+
+    pub fn palette2(&self, t: f32) -> Vector3 {
+        match &self.pal2_ramp {
+            Some(ramp) => ramp.sample(t),
+            None => palette_cosine(t, self.pal2_a, self.pal2_b, self.pal2_c, self.pal2_d),
         }
     }
 }
@@ -122,19 +284,19 @@ fn rotate2(p: Vector2, angle: f32) -> Vector2 {
 fn uv_from_pos(pos: Vector2, res: Vector2) -> Vector2 {
     Vector2::new(pos.x / res.x - 0.5, pos.y / res.y - 0.5)
 }
-fn pattern_checker(uv: Vector2, scale: f32, angle: f32) -> f32 {
+pub(crate) fn pattern_checker(uv: Vector2, scale: f32, angle: f32) -> f32 {
     let suv = rotate2(uv * scale, angle);
     let cx = suv.x.floor();
     let cy = suv.y.floor();
     let parity = ((cx as i32) + (cy as i32)) & 1;
     if parity == 0 { 0.0 } else { 1.0 }
 }
-fn pattern_rings(uv: Vector2, freq: f32, speed: f32, time: f32) -> f32 {
+pub(crate) fn pattern_rings(uv: Vector2, freq: f32, speed: f32, time: f32) -> f32 {
     let r = (uv.x * uv.x + uv.y * uv.y).sqrt();
     let phase = r * freq - time * speed;
     ((phase * std::f32::consts::TAU).sin() * 0.5 + 0.5) // 0..1
 }
-fn pattern_stripes(uv: Vector2, angle: f32, freq: f32, speed: f32, time: f32) -> f32 {
+pub(crate) fn pattern_stripes(uv: Vector2, angle: f32, freq: f32, speed: f32, time: f32) -> f32 {
     let dir = Vector2::new(angle.cos(), angle.sin());
     let t = uv.x * dir.x + uv.y * dir.y;
     let phase = t * freq - time * speed;
@@ -144,7 +306,7 @@ fn overlay_scanlines(pos: Vector2, intensity: f32) -> f32 {
     let s = ((pos.y * std::f32::consts::PI / 2.0).sin() * 0.5 + 0.5);
     1.0 - intensity * (1.0 - s) // 1.0 → sin efecto
 }
-fn palette_cosine(t: f32, a: Vector3, b: Vector3, c: Vector3, d: Vector3) -> Vector3 {
+pub(crate) fn palette_cosine(t: f32, a: Vector3, b: Vector3, c: Vector3, d: Vector3) -> Vector3 {
     Vector3::new(
         a.x + b.x * ( (6.28318 * (c.x * t + d.x)).cos() ),
         a.y + b.y * ( (6.28318 * (c.y * t + d.y)).cos() ),
@@ -152,61 +314,103 @@ fn palette_cosine(t: f32, a: Vector3, b: Vector3, c: Vector3, d: Vector3) -> Vec
     )
 }
 
-// -------------------- Fragment Shader (4 capas) --------------------
-/// L1: albedo/paleta | L2: bandas/estrías | L3: ruido/perturbación | L4: scanlines/brillo
-/// `layers` (l1,l2,l3,l4) y `mat` definen cómo luce cada entidad.
-pub fn fragment_shader(fragment: &Fragment, u: &Uniforms, layers: (bool, bool, bool, bool), mat: &Material) -> Vector3 {
-    let (l1, l2, l3, l4) = layers;
-    let pos = Vector2::new(fragment.position.x, fragment.position.y);               // pixel coords
-    let res = u.resolution;
-    let uv = uv_from_pos(pos, res);            // [-.5, .5]
-    let time = u.time;
-
-    // Base color del fragmento (0..1)
-    let base = fragment.color;
-    let mut col = base;
-    // ... arriba no cambies nada ...
-
-    // ✅ Normaliza si entró en escala 0..255 (defensa)
-    if col.x > 1.0 || col.y > 1.0 || col.z > 1.0 {
-        col = Vector3::new(col.x / 255.0, col.y / 255.0, col.z / 255.0);
+/// Height field behind `Material::bump`, in object-space units along the normal
+fn surface_height(p: Vector3, bump: &BumpParams, mat: &Material) -> f32 {
+    let mut h = 0.0;
+    if bump.amp != 0.0 {
+        h += bump.amp * noise3(bump.basis, p * bump.freq, bump.octaves, bump.lacunarity, bump.gain, mat.seed);
+    }
+    if let (true, Some(terrain)) = (bump.terrain_amp != 0.0, &mat.terrain) {
+        h += bump.terrain_amp * (terrain_height(p, terrain, mat.seed) - terrain.sea_level).max(0.0);
+    }
+    if let (true, Some(craters)) = (bump.craters, &mat.craters) {
+        h += crater_field(p, craters, mat.seed).height * p.length();
+    }
+    if let Some(map) = mat.height_map.as_ref().filter(|m| m.mapping == UvMapping::Equirect) {
+        h += map.strength * (map.texture.sample(equirect_uv(p)).x - 0.5);
     }
+    h
+}
 
-    // ---- L1: Paletas ----
-    if l1 {
-        let ang = (uv.y).atan2(uv.x); // [-pi,pi]
-        let t = ((ang + time * 0.2) / std::f32::consts::TAU + 0.5) % 1.0;
-        let pal1 = palette_cosine(t, mat.pal1_a, mat.pal1_b, mat.pal1_c, mat.pal1_d);
-        let pal2 = palette_cosine(t, mat.pal2_a, mat.pal2_b, mat.pal2_c, mat.pal2_d);
-        let r = (uv.x * uv.x + uv.y * uv.y).sqrt();
-        let mix_r = (r / mat.pal_mix_radius).clamp(0.0, 1.0);
-        let pal = pal1 * (1.0 - mix_r) + pal2 * mix_r;
-        col = col * 0.2 + pal * 0.8;
+/// Relief the fragment stage adds on top of the mesh (`bump` sources and `height_map`), 0 if none
+pub fn relief_height(p: Vector3, mat: &Material) -> f32 {
+    match (&mat.bump, &mat.height_map) {
+        (Some(bump), _) => surface_height(p, bump, mat),
+        (None, Some(_)) => surface_height(p, &BumpParams::noise(0.0, 1.0, 1, NoiseBasis::Perlin), mat),
+        (None, None) => 0.0,
     }
+}
 
-    // ---- L2: Bandas / Estrías ----
-    if l2 {
-        let rings = if mat.rings_weight > 0.0 { pattern_rings(uv, mat.rings_freq, mat.rings_speed, time) } else { 0.0 };
-        let stripes = if mat.stripes_weight > 0.0 { pattern_stripes(uv, mat.stripes_angle_deg.to_radians(), mat.stripes_freq, mat.stripes_speed, time) } else { 0.0 };
-        let checker = if mat.checker_weight > 0.0 { pattern_checker(uv, mat.checker_scale, time * 0.15) } else { 0.0 };
-        let bands = (mat.rings_weight * rings + mat.stripes_weight * stripes + mat.checker_weight * checker)
-            .clamp(0.0, 1.0);
-        col = col * (1.0 - mat.accent_strength * bands) + mat.accent * (mat.accent_strength * bands);
+/// Samples `map` at this fragment, picking the mip level from the per-pixel change of its UVs
+fn sample_map(map: &TextureMap, fragment: &Fragment) -> Vector3 {
+    match map.mapping {
+        UvMapping::Mesh => map.texture.sample_grad(fragment.uv, fragment.uv_dx, fragment.uv_dy),
+        UvMapping::Equirect => {
+            let uv = equirect_uv(fragment.obj_pos);
+            // Finite differences one pixel over; longitude wraps at the seam
+            let step = |dp: Vector3| {
+                let d = equirect_uv(fragment.obj_pos + dp) - uv;
+                Vector2::new(d.x - d.x.round(), d.y)
+            };
+            map.texture.sample_grad(uv, step(fragment.dp_dx), step(fragment.dp_dy))
+        }
     }
+}
 
-    // ---- L3: Ruido / Perturbación ----
-    if l3 {
-        let f = fbm3(Vector3::new(uv.x * mat.fbm_freq, uv.y * mat.fbm_freq, time * mat.fbm_time), mat.fbm_octaves, mat.fbm_lacunarity, mat.fbm_gain) * 0.5 + 0.5;
-        let sat = mat.sat_base + mat.sat_amp * f;
-        let bri = mat.bri_base + mat.bri_amp * f;
-        col = Vector3::new(col.x * sat, col.y * sat, col.z * sat) * bri;
+/// World-space shading normal: the interpolated normal, tilted by `bump` and `normal_map` if set
+fn shading_normal(fragment: &Fragment, u: &Uniforms, mat: &Material) -> Vector3 {
+    let mut n = fragment.normal.normalized();
+    // A height map alone still needs the finite-difference setup of a (zero-noise) bump
+    let bump = mat.bump.or_else(|| mat.height_map.as_ref().map(|_| BumpParams::noise(0.0, 1.0, 1, NoiseBasis::Perlin)));
+    if let Some(bump) = &bump {
+        // Into object space (transpose of the model rotation), bump there, and back
+        let m = &u.model;
+        let n_obj = Vector3::new(
+            m.m0 * n.x + m.m1 * n.y + m.m2 * n.z,
+            m.m4 * n.x + m.m5 * n.y + m.m6 * n.z,
+            m.m8 * n.x + m.m9 * n.y + m.m10 * n.z,
+        ).normalized();
+        let b = bumped_normal(fragment.obj_pos, n_obj, bump.strength, bump.epsilon, |p| surface_height(p, bump, mat));
+        let w = multiply_matrix_vector4(m, &Vector4::new(b.x, b.y, b.z, 0.0));
+        n = Vector3::new(w.x, w.y, w.z).normalized();
+    }
+    if let Some(map) = &mat.normal_map {
+        n = normal_mapped(n, fragment.tangent, fragment.bitangent, map.texture.sample_grad(fragment.uv, fragment.uv_dx, fragment.uv_dy), map.strength);
     }
+    n
+}
+
+// -------------------- Fragment Shader (pila de capas) --------------------
+const AMBIENT: f32 = 0.05; // fill light so night sides are not pure black
+const WATER_ROUGHNESS: f32 = 0.12; // open ocean on terrain bodies: a tight sun glint
+
+/// Composita `mat.layers` en orden sobre el color base; la luz de la estrella se aplica
+/// justo antes de la primera capa de pantalla (scanlines/bloom), o al final si no hay ninguna.
+pub fn fragment_shader(fragment: &Fragment, u: &Uniforms, mat: &Material) -> Vector3 {
+    shade(fragment, u, mat, true)
+}
+
+/// Same layer stack without the star's light or emission: the surface color for texture baking
+pub fn fragment_albedo(fragment: &Fragment, u: &Uniforms, mat: &Material) -> Vector3 {
+    shade(fragment, u, mat, false)
+}
 
-    // ---- L4: Scanlines / Bloom ----
-    if l4 {
-        let scan = overlay_scanlines(pos, mat.scan_intensity);
-        let boost = (col.x.max(col.y).max(col.z)).powf(2.0) * mat.bloom_strength;
-        col = col * scan + Vector3::new(boost, boost, boost);
+fn shade(fragment: &Fragment, u: &Uniforms, mat: &Material, lit: bool) -> Vector3 {
+    // Las capas parten de negro: el albedo procedural (capa Surface) es el único color base
+    let mut col = Vector3::zero();
+
+    let terrain_here = mat.terrain.as_ref().map(|t| terrain_sample(fragment.obj_pos, t, mat.seed));
+    let mut lighting_done = false;
+    for layer in mat.layers.iter().filter(|l| l.enabled) {
+        if layer.kind.is_post() && !lighting_done {
+            col = apply_lighting(col, fragment, u, mat, terrain_here.as_ref(), lit);
+            lighting_done = true;
+        }
+        let (src, coverage) = layer_color(&layer.kind, col, fragment, u, mat, terrain_here.as_ref());
+        col = composite(col, src, layer.blend, layer.opacity * coverage);
+    }
+    if !lighting_done {
+        col = apply_lighting(col, fragment, u, mat, terrain_here.as_ref(), lit);
     }
 
     // Gamma ligera
@@ -223,4 +427,213 @@ pub fn fragment_shader(fragment: &Fragment, u: &Uniforms, layers: (bool, bool, b
         col.y.powf(1.0 / 1.2),
         col.z.powf(1.0 / 1.2),
     )
-}
\ No newline at end of file
+}
+
+/// Color a layer puts over `below`, and how much of the pixel it covers (0..1)
+fn layer_color(kind: &LayerKind, below: Vector3, fragment: &Fragment, u: &Uniforms, mat: &Material, terrain_here: Option<&TerrainSample>) -> (Vector3, f32) {
+    let pos = Vector2::new(fragment.position.x, fragment.position.y); // pixel coords
+    let uv = uv_from_pos(pos, u.resolution);                          // [-.5, .5]
+    let time = u.time;
+
+    match kind {
+        LayerKind::Surface => (surface_color(uv, fragment, u, mat, terrain_here), 1.0),
+        LayerKind::Bands(b) => {
+            let rings = if b.rings_weight > 0.0 { pattern_rings(uv, b.rings_freq, b.rings_speed, time) } else { 0.0 };
+            let stripes = if b.stripes_weight > 0.0 { pattern_stripes(uv, b.stripes_angle_deg.to_radians(), b.stripes_freq, b.stripes_speed, time) } else { 0.0 };
+            let checker = if b.checker_weight > 0.0 { pattern_checker(uv, b.checker_scale, time * 0.15) } else { 0.0 };
+            let bands = (b.rings_weight * rings + b.stripes_weight * stripes + b.checker_weight * checker)
+                .clamp(0.0, 1.0);
+            (b.accent, b.accent_strength * bands)
+        }
+        LayerKind::Noise(n) => {
+            let f = noise3(n.basis, Vector3::new(uv.x * n.freq, uv.y * n.freq, time * n.time), n.octaves, n.lacunarity, n.gain, mat.seed) * 0.5 + 0.5;
+            let sat = n.sat_base + n.sat_amp * f;
+            let bri = n.bri_base + n.bri_amp * f;
+            (Vector3::one() * (sat * bri), 1.0)
+        }
+        LayerKind::Scanlines { intensity } => (Vector3::one() * overlay_scanlines(pos, *intensity), 1.0),
+        LayerKind::Bloom { strength } => {
+            let boost = (below.x.max(below.y).max(below.z)).powf(2.0) * strength;
+            (Vector3::new(boost, boost, boost), 1.0)
+        }
+        LayerKind::Graph(graph) => graph.eval(&GraphInputs {
+            position: fragment.obj_pos,
+            normal: fragment.normal.normalized(),
+            uv,
+            time,
+            seed: mat.seed,
+        }),
+        LayerKind::Expr(program) => program.eval(&ExprInputs {
+            position: fragment.obj_pos,
+            normal: fragment.normal.normalized(),
+            world: fragment.world_pos,
+            uv,
+            frag: pos,
+            time,
+            resolution: u.resolution,
+            base: below,
+            material: mat,
+        }),
+    }
+}
+
+/// The material's own albedo: object-space models (star, corona, rings, clouds, terrain, gas giant)
+/// or the screen-space cosine palettes, then craters and the albedo map
+fn surface_color(uv: Vector2, fragment: &Fragment, u: &Uniforms, mat: &Material, terrain_here: Option<&TerrainSample>) -> Vector3 {
+    let time = u.time;
+    let ang = (uv.y).atan2(uv.x); // [-pi,pi]
+    let t = ((ang + time * 0.2) / std::f32::consts::TAU + 0.5) % 1.0;
+    let pal1 = mat.palette1(t);
+    let pal2 = mat.palette2(t);
+    let r = (uv.x * uv.x + uv.y * uv.y).sqrt();
+    let mix_r = (r / mat.pal_mix_radius).clamp(0.0, 1.0);
+    let pal = pal1 * (1.0 - mix_r) + pal2 * mix_r;
+    // Terrain, gas giant and star bodies are colored in object space instead
+    let mut col = if let Some(star) = &mat.star {
+        let view = (u.camera_pos - fragment.world_pos).normalized();
+        star_surface(fragment.obj_pos, fragment.normal.normalized().dot(view), star, mat.seed, time)
+    } else if let Some(corona) = &mat.corona {
+        let view = (u.camera_pos - fragment.world_pos).normalized();
+        corona_glow(fragment.normal.normalized(), view, corona, mat.seed, time)
+    } else if let Some(rings) = &mat.rings {
+        let view = (u.camera_pos - fragment.world_pos).normalized();
+        let light = (u.light_pos - fragment.world_pos).normalized();
+        ring_color(fragment.obj_pos, fragment.normal, view, light, u.light_dir_obj, rings, mat.seed) * u.light_color
+    } else if let Some(clouds) = &mat.clouds {
+        clouds.color
+    } else if let Some(terrain) = terrain_here {
+        terrain.albedo
+    } else if let Some(gas) = &mat.gas {
+        gas_giant_color(fragment.obj_pos, gas, mat.seed, time)
+    } else {
+        pal
+    };
+    if let Some(map) = &mat.albedo_map {
+        let a = map.strength.clamp(0.0, 1.0);
+        col = col * (1.0 - a) + sample_map(map, fragment) * a;
+    }
+
+    // Darker crater floors + bright ray systems from the same field the vertex stage displaced
+    if let Some(craters) = &mat.craters {
+        let c = crater_field(fragment.obj_pos, craters, mat.seed);
+        col = col * (1.0 - mat.crater_floor_dark * c.floor) + Vector3::one() * (mat.crater_ray_bright * c.rays);
+    }
+    col
+}
+
+/// Diffuse (+ specular map) light from the scene's star, tinted by its blackbody color, then emission.
+/// Stars and coronae emit their own light and rings scatter it themselves; they skip the lighting.
+/// Unlit (`lit` false) returns `col` as is, for baking.
+fn apply_lighting(mut col: Vector3, fragment: &Fragment, u: &Uniforms, mat: &Material, terrain_here: Option<&TerrainSample>, lit: bool) -> Vector3 {
+    if !lit {
+        return col;
+    }
+    // Light emitted by the surface itself; added after lighting so it shows on the night side
+    let mut emissive = Vector3::zero();
+    if mat.emissive < 1.0 && mat.rings.is_none() {
+        let albedo = col;
+        let l = (u.light_pos - fragment.world_pos).normalized();
+        let (diffuse, specular, cos_sun) = if mat.shading == ShadingMode::Gouraud {
+            // Interpolated from the vertices, which had no albedo for a metal's Fresnel: tint here
+            let tint = Vector3::one() + (albedo - Vector3::one()) * mat.metallic;
+            (fragment.light_diffuse, fragment.light_specular * tint, fragment.normal.normalized().dot(l))
+        } else {
+            let n = shading_normal(fragment, u, mat);
+            let water = terrain_here.map_or(0.0, |t| t.water);
+            let at = SurfacePoint { obj_pos: fragment.obj_pos, world_pos: fragment.world_pos, normal: n, geometric: fragment.normal };
+            let (d, s) = light_terms(at, albedo, u, mat, water);
+            (d, s, n.dot(l))
+        };
+        let specular_weight = mat.specular_map.as_ref().map_or(1.0, |map| sample_map(map, fragment).x * map.strength);
+        let reflected = albedo * diffuse + specular * specular_weight;
+        col = (reflected + albedo * AMBIENT) * (1.0 - mat.emissive) + albedo * mat.emissive;
+
+        if let Some(cities) = &mat.city_lights {
+            let land = terrain_here.map_or(1.0, |t| t.land);
+            emissive += city_lights(fragment.obj_pos, land, cos_sun, cities, mat.seed);
+        }
+    }
+    if let Some(map) = &mat.emission_map {
+        emissive += sample_map(map, fragment) * map.strength;
+    }
+    col + emissive
+}
+
+/// Gouraud shading: the star's light at a vertex as (diffuse weight, specular), for the rasterizer
+/// to interpolate. Metals use a white Fresnel here; `apply_lighting` tints it with the albedo.
+pub fn vertex_lighting(obj_pos: Vector3, world_pos: Vector3, normal: Vector3, u: &Uniforms, mat: &Material) -> (Vector3, Vector3) {
+    if mat.emissive >= 1.0 || mat.rings.is_some() {
+        return (Vector3::zero(), Vector3::zero());
+    }
+    let water = mat.terrain.as_ref().map_or(0.0, |t| terrain_sample(obj_pos, t, mat.seed).water);
+    light_terms(SurfacePoint { obj_pos, world_pos, normal, geometric: normal }, Vector3::one(), u, mat, water)
+}
+
+/// A point being lit, at a vertex or a fragment
+#[derive(Clone, Copy)]
+struct SurfacePoint {
+    obj_pos: Vector3,
+    world_pos: Vector3,
+    normal: Vector3,    // shading normal (bump / normal maps applied)
+    geometric: Vector3, // interpolated mesh normal, which decides the visible side
+}
+
+/// Star light reflected toward the camera at `at`: the weight on the albedo, and the specular added on top
+fn light_terms(at: SurfacePoint, albedo: Vector3, u: &Uniforms, mat: &Material, water: f32) -> (Vector3, Vector3) {
+    let view = (u.camera_pos - at.world_pos).normalized();
+    let n = if at.geometric.dot(view) < 0.0 { -at.normal } else { at.normal }; // two-sided
+    let l = (u.light_pos - at.world_pos).normalized();
+    let mut light = u.light_color;
+    if let Some(clouds) = &mat.cloud_shadow {
+        light = light * cloud_shadow(at.obj_pos, u.light_dir_obj, clouds, u.time);
+    }
+    // Open water is glossy and reflects by the terrain's `ocean_specular`, so oceans glint
+    let (mut roughness, mut specular) = (mat.roughness, mat.specular);
+    if let Some(t) = &mat.terrain {
+        roughness += (WATER_ROUGHNESS - roughness) * water;
+        specular += (t.ocean_specular - specular) * water;
+    }
+    let (diffuse, spec) = cook_torrance(albedo, n, view, l, roughness, mat.metallic, specular);
+    (diffuse * light, spec * light)
+}
+
+/// Cook-Torrance with a GGX distribution, Smith-Schlick geometry and Schlick Fresnel, times n.l,
+/// as (diffuse weight on the albedo, specular). `albedo` only sets a metal's reflectance.
+/// Light is scaled so a matte dielectric gives the plain Lambert `albedo * n.l` the scene is tuned for.
+fn cook_torrance(albedo: Vector3, n: Vector3, v: Vector3, l: Vector3, roughness: f32, metallic: f32, specular: f32) -> (Vector3, Vector3) {
+    let n_l = n.dot(l);
+    if n_l <= 0.0 {
+        return (Vector3::zero(), Vector3::zero());
+    }
+    let n_v = n.dot(v).max(1e-4);
+    let h = (l + v).normalized();
+    let n_h = n.dot(h).max(0.0);
+    let v_h = v.dot(h).max(0.0);
+
+    let a = (roughness * roughness).max(1e-3);
+    let a2 = a * a;
+    let denom = n_h * n_h * (a2 - 1.0) + 1.0;
+    let distribution = a2 / (PI * denom * denom);
+    let k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+    let geometry = n_l / (n_l * (1.0 - k) + k) * (n_v / (n_v * (1.0 - k) + k));
+
+    let dielectric = Vector3::one() * (0.08 * specular);
+    let f0 = dielectric + (albedo - dielectric) * metallic;
+    let fresnel = f0 + (Vector3::one() - f0) * (1.0 - v_h).powi(5);
+
+    let spec = fresnel * (distribution * geometry / (4.0 * n_l * n_v) * PI);
+    let diffuse = (Vector3::one() - fresnel) * (1.0 - metallic);
+    (diffuse * n_l, spec * n_l)
+}
+
+/// Coverage of a fragment for `BlendMode::Alpha` materials (1 = fully opaque)
+pub fn fragment_alpha(fragment: &Fragment, u: &Uniforms, mat: &Material) -> f32 {
+    if let Some(clouds) = &mat.clouds {
+        return cloud_alpha(fragment.obj_pos, clouds, u.time);
+    }
+    if let Some(rings) = &mat.rings {
+        let view = (u.camera_pos - fragment.world_pos).normalized();
+        return ring_alpha(fragment.obj_pos, fragment.normal, view, rings, mat.seed);
+    }
+    1.0
+}
@@ -0,0 +1,185 @@
+// src/sky.rs
+use raylib::prelude::*;
+
+use crate::blackbody::blackbody_rgb;
+use crate::procedural::fbm3;
+
+/// Procedural sky (stars + nebula) evaluated by view direction.
+/// Only depends on the direction, so it stays fixed at "infinity" and rotates with the camera.
+#[derive(Clone)]
+pub struct Sky {
+    pub seed: u32,
+    pub base: Vector3,              // deep-space color behind everything (0..1)
+
+    // Stars: two hashed layers (sparse/bright + dense/faint)
+    pub star_density: f32,          // cells per unit of direction for the dense layer
+    pub star_probability: f32,      // chance that a cell holds a star
+    pub magnitude_limit: f32,       // faintest apparent magnitude drawn
+    pub star_radius: f32,           // point spread radius in cell units
+    pub star_brightness: f32,       // global multiplier on 10^(-0.4 m)
+
+    // Nebula: domain-warped FBM, baked into an equirectangular table
+    pub nebula_freq: f32,
+    pub nebula_octaves: u32,
+    pub nebula_warp: f32,
+    pub nebula_intensity: f32,
+    pub nebula_color_a: Vector3,
+    pub nebula_color_b: Vector3,
+
+    nebula: Vec<Vector3>,           // baked nebula (NEB_W x NEB_H), filled by `bake`
+}
+
+const NEB_W: usize = 512;
+const NEB_H: usize = 256;
+
+impl Sky {
+    pub fn new(seed: u32) -> Self {
+        let mut sky = Self {
+            seed,
+            base: Vector3::new(4.0 / 255.0, 12.0 / 255.0, 36.0 / 255.0) * 0.35,
+            star_density: 260.0,
+            star_probability: 0.035,
+            magnitude_limit: 6.5,
+            star_radius: 0.32,
+            star_brightness: 1.0,
+            nebula_freq: 1.6,
+            nebula_octaves: 5,
+            nebula_warp: 1.4,
+            nebula_intensity: 0.22,
+            nebula_color_a: Vector3::new(0.35, 0.10, 0.45),
+            nebula_color_b: Vector3::new(0.05, 0.30, 0.55),
+            nebula: Vec::new(),
+        };
+        sky.bake();
+        sky
+    }
+
+    /// (Re)builds the nebula table; call after changing nebula parameters.
+    pub fn bake(&mut self) {
+        let mut table = Vec::with_capacity(NEB_W * NEB_H);
+        for j in 0..NEB_H {
+            let phi = (j as f32 + 0.5) / NEB_H as f32 * std::f32::consts::PI;
+            for i in 0..NEB_W {
+                let theta = (i as f32 + 0.5) / NEB_W as f32 * std::f32::consts::TAU;
+                let dir = Vector3::new(phi.sin() * theta.cos(), phi.cos(), phi.sin() * theta.sin());
+                table.push(self.nebula_at(dir));
+            }
+        }
+        self.nebula = table;
+    }
+
+    /// Final sky color (0..1, may exceed 1 on bright stars) for a world-space view direction.
+    pub fn sample(&self, dir: Vector3) -> Vector3 {
+        let d = dir.normalized();
+        let mut col = self.base + self.nebula_lookup(d);
+        // Bright, sparse layer first; dense layer adds the faint background
+        col += self.star_layer(d, self.star_density * 0.35, self.star_probability * 0.6, self.seed ^ 0xA511_E9B3);
+        col += self.star_layer(d, self.star_density, self.star_probability, self.seed);
+        col
+    }
+
+    fn nebula_at(&self, d: Vector3) -> Vector3 {
+        let s = self.seed as f32 * 0.0137 % 97.0; // offsets the field per seed
+        let p = d * self.nebula_freq + Vector3::new(s, s * 0.61, s * 0.37);
+        // Domain warp (IQ style): q warps r, r warps the final density
+        let q = Vector3::new(
+            fbm3(p, self.nebula_octaves, 2.0, 0.5, self.seed),
+            fbm3(p + Vector3::new(5.2, 1.3, 2.8), self.nebula_octaves, 2.0, 0.5, self.seed),
+            fbm3(p + Vector3::new(1.7, 9.2, 4.1), self.nebula_octaves, 2.0, 0.5, self.seed),
+        );
+        let r = p + q * self.nebula_warp;
+        let f = fbm3(r, self.nebula_octaves, 2.0, 0.5, self.seed) * 0.5 + 0.5;
+        let density = smooth(0.45, 0.85, f);
+        let t = (q.x * 0.5 + 0.5).clamp(0.0, 1.0);
+        let tint = self.nebula_color_a * (1.0 - t) + self.nebula_color_b * t;
+        tint * (density * density * self.nebula_intensity)
+    }
+
+    fn nebula_lookup(&self, d: Vector3) -> Vector3 {
+        if self.nebula.is_empty() {
+            return Vector3::zero();
+        }
+        // Equirectangular coordinates matching `bake`
+        let phi = d.y.clamp(-1.0, 1.0).acos();
+        let mut theta = d.z.atan2(d.x);
+        if theta < 0.0 { theta += std::f32::consts::TAU; }
+        let fx = theta / std::f32::consts::TAU * NEB_W as f32 - 0.5;
+        let fy = (phi / std::f32::consts::PI * NEB_H as f32 - 0.5).clamp(0.0, (NEB_H - 1) as f32);
+        let x0 = fx.floor();
+        let y0 = fy.floor() as usize;
+        let tx = fx - x0;
+        let ty = fy - y0 as f32;
+        let x0 = (x0 as i32).rem_euclid(NEB_W as i32) as usize;
+        let x1 = (x0 + 1) % NEB_W;
+        let y1 = (y0 + 1).min(NEB_H - 1);
+        let at = |x: usize, y: usize| self.nebula[y * NEB_W + x];
+        let top = at(x0, y0) * (1.0 - tx) + at(x1, y0) * tx;
+        let bot = at(x0, y1) * (1.0 - tx) + at(x1, y1) * tx;
+        top * (1.0 - ty) + bot * ty
+    }
+
+    fn star_layer(&self, d: Vector3, density: f32, probability: f32, seed: u32) -> Vector3 {
+        let p = d * density;
+        let cell = (p.x.floor() as i32, p.y.floor() as i32, p.z.floor() as i32);
+        let h = cell_hash(cell.0, cell.1, cell.2, seed);
+        if unit(h) > probability {
+            return Vector3::zero();
+        }
+        // Jittered star position kept away from cell borders, projected onto the sphere
+        let h1 = hash_u32(h);
+        let h2 = hash_u32(h1);
+        let h3 = hash_u32(h2);
+        let local = Vector3::new(0.3 + 0.4 * unit(h1), 0.3 + 0.4 * unit(h2), 0.3 + 0.4 * unit(h3));
+        let star = (Vector3::new(cell.0 as f32, cell.1 as f32, cell.2 as f32) + local).normalized();
+        let sp = star * density;
+        // Reject stars whose projection lands in another cell (avoids duplicates)
+        if (sp.x.floor() as i32, sp.y.floor() as i32, sp.z.floor() as i32) != cell {
+            return Vector3::zero();
+        }
+        let dist = (p - sp).length();
+        if dist > self.star_radius {
+            return Vector3::zero();
+        }
+
+        // Magnitude from N(<m) ∝ 10^(0.6 m): most stars are faint, few are bright
+        let h4 = hash_u32(h3);
+        let u = unit(h4).max(1e-6);
+        let mag = self.magnitude_limit + u.log10() / 0.6;
+        let flux = 10f32.powf(-0.4 * mag) * self.star_brightness * 100.0;
+
+        // Color temperature (K): cool stars dominate
+        let h5 = hash_u32(h4);
+        let kelvin = 2800.0 + 9000.0 * unit(h5).powf(2.2);
+        let fall = 1.0 - dist / self.star_radius;
+        blackbody_rgb(kelvin) * (flux * fall * fall)
+    }
+}
+
+#[inline]
+fn smooth(e0: f32, e1: f32, x: f32) -> f32 {
+    let t = ((x - e0) / (e1 - e0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+#[inline]
+fn hash_u32(mut x: u32) -> u32 {
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7FEB_352D);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846C_A68B);
+    x ^= x >> 16;
+    x
+}
+
+#[inline]
+fn cell_hash(i: i32, j: i32, k: i32, seed: u32) -> u32 {
+    let mut h = seed;
+    h = hash_u32(h ^ i as u32);
+    h = hash_u32(h ^ j as u32);
+    hash_u32(h ^ k as u32)
+}
+
+#[inline]
+fn unit(h: u32) -> f32 {
+    (h >> 8) as f32 / (1u32 << 24) as f32 // 0..1
+}
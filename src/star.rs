@@ -0,0 +1,157 @@
+// src/star.rs
+use raylib::prelude::*;
+
+use crate::blackbody::blackbody_rgb;
+use crate::procedural::{hash_to_unit, hash_u32, lerp, noise3, smoothstep, worley_noise3, NoiseBasis};
+
+/// Stellar photosphere: temperature-driven color, limb darkening, convective
+/// granulation and seeded sunspot groups.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StarParams {
+    pub temperature: f32,       // effective temperature (K)
+    pub intensity: f32,         // brightness at disk center (>1 saturates to white-hot)
+
+    // Quadratic limb darkening: I(mu)/I(1) = 1 - u1 (1 - mu) - u2 (1 - mu)^2
+    pub limb_u1: f32,
+    pub limb_u2: f32,
+
+    // Granulation: Worley cells with dark intergranular lanes, slowly evolving
+    pub granule_freq: f32,
+    pub granule_speed: f32,
+    pub granule_contrast: f32,
+
+    // Sunspot groups within an active latitude belt
+    pub spot_groups: u32,
+    pub spots_per_group: u32,
+    pub spot_size: f32,         // penumbra radius of the largest spot (unit-sphere units)
+    pub spot_max_latitude: f32, // |sin(latitude)| of the active belt edge
+    pub umbra_darkness: f32,    // remaining intensity in the umbra (0..1)
+    pub penumbra_darkness: f32, // remaining intensity in the penumbra (0..1)
+}
+
+impl StarParams {
+    pub fn sun() -> Self {
+        Self {
+            temperature: 5772.0,
+            intensity: 1.15,
+            limb_u1: 0.47,
+            limb_u2: 0.23,
+            granule_freq: 22.0,
+            granule_speed: 0.08,
+            granule_contrast: 0.18,
+            spot_groups: 5,
+            spots_per_group: 4,
+            spot_size: 0.07,
+            spot_max_latitude: 0.55,
+            umbra_darkness: 0.22,
+            penumbra_darkness: 0.62,
+        }
+    }
+}
+
+/// Additive glow around a star, shaded on a larger transparent shell mesh.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CoronaParams {
+    pub temperature: f32,     // tint, usually the star's own temperature
+    pub shell_scale: f32,     // shell radius / star radius
+    pub intensity: f32,
+    pub falloff: f32,         // e-folding distance in star radii beyond the limb
+    pub streamer_freq: f32,   // angular frequency of coronal streamers / flares
+    pub streamer_amount: f32,
+    pub streamer_speed: f32,
+}
+
+impl CoronaParams {
+    pub fn sun() -> Self {
+        Self {
+            temperature: 5772.0,
+            shell_scale: 1.8,
+            intensity: 0.9,
+            falloff: 0.18,
+            streamer_freq: 5.0,
+            streamer_amount: 0.7,
+            streamer_speed: 0.15,
+        }
+    }
+}
+
+/// Photosphere color at an object-space point. `mu` is cos(angle between normal and view).
+pub fn star_surface(p: Vector3, mu: f32, s: &StarParams, seed: u32, time: f32) -> Vector3 {
+    let dir = p.normalized();
+    let mu = mu.clamp(0.0, 1.0);
+
+    // Limb darkening; near the limb we also see higher, cooler layers (Eddington: T^4 ∝ 2 + 3 mu)
+    let one_m = 1.0 - mu;
+    let limb = (1.0 - s.limb_u1 * one_m - s.limb_u2 * one_m * one_m).max(0.0);
+    let t_seen = s.temperature * ((2.0 + 3.0 * mu) / 5.0).powf(0.25);
+
+    // Granulation: bright cell interiors, dark lanes where F1 ≈ F2; domain drifts to evolve cells
+    let gp = dir * s.granule_freq + Vector3::new(0.0, time * s.granule_speed, time * s.granule_speed * 0.7);
+    let (f1, f2) = worley_noise3(gp, seed);
+    let lanes = smoothstep(0.0, 0.35, f2 - f1);
+    let churn = noise3(NoiseBasis::Perlin, gp * 0.5, 2, 2.0, 0.5, seed ^ 0x64A1_0001);
+    let granules = 1.0 - s.granule_contrast * (1.0 - lanes) + 0.05 * churn;
+
+    let spot = sunspots(dir, s, seed);
+
+    blackbody_rgb(t_seen) * (s.intensity * limb * granules * spot)
+}
+
+/// Intensity multiplier from sunspot groups (1 = undisturbed photosphere)
+fn sunspots(dir: Vector3, s: &StarParams, seed: u32) -> f32 {
+    let mut out: f32 = 1.0;
+    for g in 0..s.spot_groups {
+        let mut h = hash_u32(seed ^ 0x5B07_0000 ^ g.wrapping_mul(0x9E37_79B9));
+        let mut next = || { h = hash_u32(h); hash_to_unit(h) };
+
+        // Group center inside the active belt, either hemisphere
+        let sin_lat = (0.1 + 0.9 * next()) * s.spot_max_latitude * if next() < 0.5 { -1.0 } else { 1.0 };
+        let lon = next() * std::f32::consts::TAU;
+        let cl = (1.0 - sin_lat * sin_lat).sqrt();
+        let center = Vector3::new(cl * lon.cos(), sin_lat, cl * lon.sin());
+        // Groups are strung out east-west
+        let east = Vector3::new(-lon.sin(), 0.0, lon.cos());
+
+        for k in 0..s.spots_per_group {
+            let size = s.spot_size * if k == 0 { 1.0 } else { 0.3 + 0.5 * next() };
+            let offset = east * ((next() - 0.5) * s.spot_size * 5.0) + Vector3::new(0.0, (next() - 0.5) * s.spot_size, 0.0);
+            let c = (center + offset).normalized();
+            let r = (dir - c).length() / size;
+            if r >= 1.0 {
+                continue;
+            }
+            // Umbra inside 40% of the radius, penumbra with radial filaments outside
+            let ang = (dir - c).dot(east).atan2((dir - c).y);
+            let filaments = 0.9 + 0.1 * (ang * 24.0).sin();
+            let pen = s.penumbra_darkness * filaments;
+            let level = if r < 0.4 {
+                lerp(s.umbra_darkness, pen, smoothstep(0.3, 0.4, r))
+            } else {
+                lerp(pen, 1.0, smoothstep(0.85, 1.0, r))
+            };
+            out = out.min(level);
+        }
+    }
+    out
+}
+
+/// Additive corona color on a shell fragment. `normal`/`view` are unit vectors (view points at the eye).
+pub fn corona_glow(normal: Vector3, view: Vector3, c: &CoronaParams, seed: u32, time: f32) -> Vector3 {
+    let mu = normal.dot(view);
+    if mu <= 0.0 {
+        return Vector3::zero(); // back of the shell
+    }
+    // Impact parameter of the view ray, in star radii
+    let b = c.shell_scale * (1.0 - mu * mu).max(0.0).sqrt();
+    if b < 1.0 {
+        return Vector3::zero(); // the photosphere covers this
+    }
+
+    // Streamers: noise over the position angle only, so they point radially outward
+    let perp = (normal - view * mu).normalized();
+    let streak = noise3(NoiseBasis::Perlin, perp * c.streamer_freq + Vector3::new(0.0, 0.0, time * c.streamer_speed), 3, 2.0, 0.5, seed) * 0.5 + 0.5;
+    let reach = c.falloff * (1.0 + c.streamer_amount * (streak * 2.0 - 0.3).max(0.0) * 2.0);
+    let glow = (-(b - 1.0) / reach).exp() * c.intensity;
+
+    blackbody_rgb(c.temperature) * glow
+}
@@ -0,0 +1,119 @@
+// src/terrain.rs
+use raylib::prelude::*;
+
+use crate::procedural::{mix, noise3, smoothstep, NoiseBasis};
+
+/// Earth-like surface: a height field on the sphere classified into ocean, shore,
+/// lowland biomes, mountains and snow, plus polar ice caps.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TerrainParams {
+    // Height field (object-space direction -> ~[-1,1])
+    pub height_basis: NoiseBasis,
+    pub height_freq: f32, pub height_octaves: u32, pub height_lacunarity: f32, pub height_gain: f32,
+
+    // Elevation bands (same units as the height field)
+    pub sea_level: f32,
+    pub shore_width: f32,
+    pub mountain_level: f32,
+    pub snow_level: f32,      // snow line at the equator; drops toward the poles
+    pub snow_lat_drop: f32,   // how much the snow line drops from equator to pole
+
+    // Polar caps: |latitude| above which ice covers land and sea
+    pub ice_latitude: f32,    // sin(latitude), e.g. 0.85 ~ 58°
+    pub ice_edge_noise: f32,  // ragged cap edge amplitude
+
+    // Colors
+    pub deep_ocean: Vector3, pub shallow_ocean: Vector3,
+    pub sand: Vector3,
+    pub tropical: Vector3, pub desert: Vector3, pub temperate: Vector3, pub boreal: Vector3, pub tundra: Vector3,
+    pub rock: Vector3, pub snow: Vector3,
+
+    pub ocean_specular: f32,  // strength of the sun glint on water (lighting stage)
+}
+
+impl TerrainParams {
+    pub fn earth() -> Self {
+        Self {
+            height_basis: NoiseBasis::Perlin,
+            height_freq: 1.8, height_octaves: 6, height_lacunarity: 2.0, height_gain: 0.5,
+            sea_level: 0.08,
+            shore_width: 0.025,
+            mountain_level: 0.22,
+            snow_level: 0.34,
+            snow_lat_drop: 0.35,
+            ice_latitude: 0.86,
+            ice_edge_noise: 0.06,
+            deep_ocean: Vector3::new(0.02, 0.07, 0.22), shallow_ocean: Vector3::new(0.05, 0.30, 0.45),
+            sand: Vector3::new(0.76, 0.70, 0.50),
+            tropical: Vector3::new(0.10, 0.38, 0.10), desert: Vector3::new(0.78, 0.62, 0.38),
+            temperate: Vector3::new(0.25, 0.45, 0.18), boreal: Vector3::new(0.14, 0.28, 0.16),
+            tundra: Vector3::new(0.45, 0.43, 0.36),
+            rock: Vector3::new(0.42, 0.36, 0.31), snow: Vector3::new(0.95, 0.96, 0.98),
+            ocean_specular: 0.8,
+        }
+    }
+}
+
+/// Result of classifying one surface point.
+#[derive(Clone, Copy, Debug)]
+pub struct TerrainSample {
+    pub albedo: Vector3,
+    pub height: f32,  // raw height field value
+    pub water: f32,   // 0..1 open-water coverage (ice-free ocean), drives glint
+    pub land: f32,    // 0..1 ice-free land coverage, where settlements can be
+}
+
+/// Height field at an object-space point (projected onto the unit sphere).
+pub fn terrain_height(p: Vector3, t: &TerrainParams, seed: u32) -> f32 {
+    let dir = p.normalized();
+    noise3(t.height_basis, dir * t.height_freq, t.height_octaves, t.height_lacunarity, t.height_gain, seed)
+}
+
+pub fn terrain_sample(p: Vector3, t: &TerrainParams, seed: u32) -> TerrainSample {
+    let dir = p.normalized();
+    let h = terrain_height(dir, t, seed);
+    let lat = dir.y.abs(); // 0 equator .. 1 pole
+
+    // Secondary low-frequency fields: moisture for biomes and a wobble for borders
+    let moisture = noise3(NoiseBasis::Perlin, dir * 2.7 + Vector3::new(17.0, 3.0, 9.0), 3, 2.0, 0.5, seed ^ 0x5EED_0001) * 0.5 + 0.5;
+    let wobble = noise3(NoiseBasis::Perlin, dir * 9.0, 2, 2.0, 0.5, seed ^ 0x5EED_0002);
+
+    let mut albedo;
+    let mut water = 0.0;
+    if h < t.sea_level {
+        // Ocean: shallow near the coast, deep further out
+        let depth = ((t.sea_level - h) / 0.35).clamp(0.0, 1.0);
+        albedo = mix(t.shallow_ocean, t.deep_ocean, smoothstep(0.0, 1.0, depth));
+        water = 1.0;
+    } else {
+        let above = h - t.sea_level;
+        albedo = biome_color(t, lat + wobble * 0.05, moisture);
+
+        // Beaches right above sea level
+        albedo = mix(t.sand, albedo, smoothstep(0.0, t.shore_width, above));
+
+        // Rock on high ground, snow above a snow line that drops toward the poles
+        albedo = mix(albedo, t.rock, smoothstep(t.mountain_level - 0.06, t.mountain_level + 0.06, h));
+        let snow_line = t.snow_level - t.snow_lat_drop * lat;
+        albedo = mix(albedo, t.snow, smoothstep(snow_line - 0.03, snow_line + 0.03, h + wobble * 0.02));
+    }
+
+    // Polar ice caps over land and sea, with a ragged edge
+    let ice = smoothstep(t.ice_latitude - 0.02, t.ice_latitude + 0.02, lat + wobble * t.ice_edge_noise);
+    albedo = mix(albedo, t.snow, ice);
+    let land = (1.0 - water) * (1.0 - ice);
+    water *= 1.0 - ice;
+
+    TerrainSample { albedo, height: h, water, land }
+}
+
+/// Lowland color from latitude (0 equator .. 1 pole) and moisture (0 dry .. 1 wet)
+fn biome_color(t: &TerrainParams, lat: f32, moisture: f32) -> Vector3 {
+    // Wet tropics -> subtropical deserts (dry belt ~20-35°) -> temperate -> boreal -> tundra
+    let tropics = mix(t.desert, t.tropical, smoothstep(0.35, 0.6, moisture));
+    let dry_belt = 1.0 - smoothstep(0.0, 0.12, (lat - 0.45).abs());
+    let subtropics = mix(tropics, t.desert, dry_belt * (1.0 - smoothstep(0.45, 0.75, moisture)));
+    let temperate = mix(subtropics, t.temperate, smoothstep(0.50, 0.60, lat));
+    let boreal = mix(temperate, t.boreal, smoothstep(0.68, 0.76, lat));
+    mix(boreal, t.tundra, smoothstep(0.78, 0.86, lat))
+}
@@ -0,0 +1,182 @@
+// src/texture.rs
+use raylib::prelude::*;
+use std::sync::Arc;
+
+/// What happens outside [0,1] on one texture axis
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WrapMode {
+    Repeat,
+    Clamp,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Filter {
+    Nearest,
+    Bilinear,   // level 0 only
+    Trilinear,  // bilinear on the two closest mip levels, blended by LOD
+}
+
+/// CPU-side image for sampling in the fragment stage (colors as 0..1 floats), with a mip chain.
+#[derive(Clone)]
+pub struct Texture {
+    pub width: u32,
+    pub height: u32,
+    pub wrap_u: WrapMode,
+    pub wrap_v: WrapMode,
+    pub filter: Filter,
+    levels: Vec<Level>,  // levels[0] is the full-size image
+}
+
+#[derive(Clone)]
+struct Level {
+    width: u32,
+    height: u32,
+    pixels: Vec<Vector3>,
+}
+
+impl Texture {
+    /// Loads any format raylib can read (PNG, BMP, TGA, JPG, ...); repeat wrap, trilinear filter
+    pub fn load(path: &str) -> Result<Self, String> {
+        let image = Image::load_image(path).map_err(|e| format!("{}: {}", path, e))?;
+        let (width, height) = (image.width() as u32, image.height() as u32);
+        let pixels = image.get_image_data().iter().map(|c| Vector3::new(
+            c.r as f32 / 255.0,
+            c.g as f32 / 255.0,
+            c.b as f32 / 255.0,
+        )).collect();
+        Ok(Self::from_pixels(width, height, pixels))
+    }
+
+    /// Builds a texture (and its mipmaps) from row-major pixels, top row first
+    pub fn from_pixels(width: u32, height: u32, pixels: Vec<Vector3>) -> Self {
+        let mut levels = vec![Level { width, height, pixels }];
+        while let Some(next) = levels.last().and_then(downsample) {
+            levels.push(next);
+        }
+        Texture { width, height, wrap_u: WrapMode::Repeat, wrap_v: WrapMode::Repeat, filter: Filter::Trilinear, levels }
+    }
+
+    /// Equirectangular maps repeat around the equator but clamp at the poles
+    pub fn equirect(mut self) -> Self {
+        self.wrap_u = WrapMode::Repeat;
+        self.wrap_v = WrapMode::Clamp;
+        self
+    }
+
+    pub fn mip_levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// Full-resolution bilinear sample; v = 0 is the bottom row (OBJ convention)
+    pub fn sample(&self, uv: Vector2) -> Vector3 {
+        self.bilinear(0, uv)
+    }
+
+    /// Filtered sample with the mip level chosen from the screen-space UV derivatives
+    /// (change of uv per pixel step in x and y)
+    pub fn sample_grad(&self, uv: Vector2, duv_dx: Vector2, duv_dy: Vector2) -> Vector3 {
+        match self.filter {
+            Filter::Nearest => self.nearest(uv),
+            Filter::Bilinear => self.bilinear(0, uv),
+            Filter::Trilinear => {
+                let (w, h) = (self.width as f32, self.height as f32);
+                let fx = Vector2::new(duv_dx.x * w, duv_dx.y * h);
+                let fy = Vector2::new(duv_dy.x * w, duv_dy.y * h);
+                // Texels covered by one pixel along its longer screen axis
+                let rho = fx.length().max(fy.length()).max(1e-8);
+                let lod = rho.log2().clamp(0.0, (self.levels.len() - 1) as f32);
+                let l0 = lod.floor() as usize;
+                let l1 = (l0 + 1).min(self.levels.len() - 1);
+                let t = lod - l0 as f32;
+                let a = self.bilinear(l0, uv);
+                if t <= 0.0 || l1 == l0 { a } else { a * (1.0 - t) + self.bilinear(l1, uv) * t }
+            }
+        }
+    }
+
+    fn nearest(&self, uv: Vector2) -> Vector3 {
+        let level = &self.levels[0];
+        let x = (uv.x * level.width as f32).floor() as i32;
+        let y = ((1.0 - uv.y) * level.height as f32).floor() as i32;
+        level.pixels[self.texel_index(level, x, y)]
+    }
+
+    fn bilinear(&self, lod: usize, uv: Vector2) -> Vector3 {
+        let level = &self.levels[lod];
+        let fx = uv.x * level.width as f32 - 0.5;
+        let fy = (1.0 - uv.y) * level.height as f32 - 0.5;
+        let (x0, y0) = (fx.floor(), fy.floor());
+        let (tx, ty) = (fx - x0, fy - y0);
+        let (x0, y0) = (x0 as i32, y0 as i32);
+        let at = |x: i32, y: i32| level.pixels[self.texel_index(level, x, y)];
+        let top = at(x0, y0) * (1.0 - tx) + at(x0 + 1, y0) * tx;
+        let bot = at(x0, y0 + 1) * (1.0 - tx) + at(x0 + 1, y0 + 1) * tx;
+        top * (1.0 - ty) + bot * ty
+    }
+
+    fn texel_index(&self, level: &Level, x: i32, y: i32) -> usize {
+        let wrap = |i: i32, n: u32, mode: WrapMode| match mode {
+            WrapMode::Repeat => i.rem_euclid(n as i32) as u32,
+            WrapMode::Clamp => i.clamp(0, n as i32 - 1) as u32,
+        };
+        let xi = wrap(x, level.width, self.wrap_u);
+        let yi = wrap(y, level.height, self.wrap_v);
+        (yi * level.width + xi) as usize
+    }
+}
+
+/// Next mip level (2x2 box filter), or None once the image is 1x1
+fn downsample(level: &Level) -> Option<Level> {
+    if level.width == 1 && level.height == 1 {
+        return None;
+    }
+    let (w, h) = ((level.width / 2).max(1), (level.height / 2).max(1));
+    let mut pixels = Vec::with_capacity((w * h) as usize);
+    for y in 0..h {
+        for x in 0..w {
+            let mut sum = Vector3::zero();
+            for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                let sx = (x * 2 + dx).min(level.width - 1);
+                let sy = (y * 2 + dy).min(level.height - 1);
+                sum += level.pixels[(sy * level.width + sx) as usize];
+            }
+            pixels.push(sum * 0.25);
+        }
+    }
+    Some(Level { width: w, height: h, pixels })
+}
+
+/// How a texture is laid over a body
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UvMapping {
+    Mesh,      // the mesh's own UVs (imported OBJ)
+    Equirect,  // longitude/latitude of the object-space position (any sphere, no UVs needed)
+}
+
+/// A texture bound to a `Material` slot
+#[derive(Clone)]
+pub struct TextureMap {
+    pub texture: Arc<Texture>,
+    pub mapping: UvMapping,
+    pub strength: f32,  // albedo: blend weight; emission/specular: multiplier; height: amplitude
+}
+
+impl TextureMap {
+    pub fn load(path: &str, mapping: UvMapping, strength: f32) -> Result<Self, String> {
+        let mut texture = Texture::load(path)?;
+        if mapping == UvMapping::Equirect {
+            texture = texture.equirect();
+        }
+        Ok(TextureMap { texture: Arc::new(texture), mapping, strength })
+    }
+}
+
+/// Equirectangular UV of an object-space point: u = longitude, v = latitude (north pole at v = 1)
+pub fn equirect_uv(p: Vector3) -> Vector2 {
+    let d = p.normalized();
+    let mut u = d.z.atan2(d.x) / std::f32::consts::TAU;
+    if u < 0.0 {
+        u += 1.0;
+    }
+    Vector2::new(u, 1.0 - d.y.clamp(-1.0, 1.0).acos() / std::f32::consts::PI)
+}
@@ -1,8 +1,9 @@
 // src/triangle.rs
 use raylib::prelude::*;
 use crate::fragment::Fragment;
-use crate::light::Light;
 use crate::line::line;
+use crate::shaders::ShadingMode;
+use crate::vertex::Vertex;
 
 fn barycentric_coordinates(p_x: f32, p_y: f32, a: &Vector3, b: &Vector3, c: &Vector3)  -> (f32, f32, f32) {
     let a_x = a.x;
@@ -25,8 +26,9 @@ fn barycentric_coordinates(p_x: f32, p_y: f32, a: &Vector3, b: &Vector3, c: &Vec
     (w, v, u)
 }
 
-pub fn triangle(v1: &Vector3, v2: &Vector3, v3: &Vector3, light: &Light) -> Vec<Fragment> {
+pub fn triangle(a: &Vertex, b: &Vertex, c: &Vertex, shading: ShadingMode) -> Vec<Fragment> {
     let mut fragments: Vec<Fragment> = Vec::new();
+    let (v1, v2, v3) = (&a.screen, &b.screen, &c.screen);
 
     let a_x = v1.x;
     let b_x = v2.x;
@@ -35,37 +37,61 @@ pub fn triangle(v1: &Vector3, v2: &Vector3, v3: &Vector3, light: &Light) -> Vec<
     let b_y = v2.y;
     let c_y = v3.y;
 
-    let color_a = Vector3::new(1.0, 0.0, 0.0);
-    let color_b = Vector3::new(0.0, 1.0, 0.0);
-    let color_c = Vector3::new(0.0, 0.0, 1.0);
-
     let min_x = a_x.min(b_x).min(c_x).floor() as i32;
     let min_y = a_y.min(b_y).min(c_y).floor() as i32;
 
     let max_x = a_x.max(b_x).max(c_x).ceil() as i32;
     let max_y = a_y.max(b_y).max(c_y).ceil() as i32;
 
-    let light = light.position.normalized();
+    // Screen-space gradients of the barycentrics (w, v, u), hence of every interpolated attribute
+    let area = (b_y - c_y) * (a_x - c_x) + (c_x - b_x) * (a_y - c_y);
+    let (dw_dx, dw_dy, dv_dx, dv_dy) = if area.abs() < 1e-10 {
+        (0.0, 0.0, 0.0, 0.0)
+    } else {
+        ((b_y - c_y) / area, (c_x - b_x) / area, (c_y - a_y) / area, (a_x - c_x) / area)
+    };
+    let (du_dx, du_dy) = (-dw_dx - dv_dx, -dw_dy - dv_dy);
+    let uv_dx = a.uv * dw_dx + b.uv * dv_dx + c.uv * du_dx;
+    let uv_dy = a.uv * dw_dy + b.uv * dv_dy + c.uv * du_dy;
+    let dp_dx = a.obj_pos * dw_dx + b.obj_pos * dv_dx + c.obj_pos * du_dx;
+    let dp_dy = a.obj_pos * dw_dy + b.obj_pos * dv_dy + c.obj_pos * du_dy;
+
+    // Flat shading: the face's own normal everywhere, turned to the side the vertex normals face
+    let face_normal = if shading == ShadingMode::Flat {
+        let n = (b.world_pos - a.world_pos).cross(c.world_pos - a.world_pos);
+        let n = if n.dot(a.normal + b.normal + c.normal) < 0.0 { -n } else { n };
+        (n.length() > 0.0).then(|| n.normalized())
+    } else {
+        None
+    };
+
     for y in min_y..=max_y {
         for x in min_x..=max_x {
             let (w, v, u) = barycentric_coordinates(x  as f32, y as f32, v1, v2, v3);
-            let normal = v1.normalized();//(v2 - v1).cross(v1 - v3).normalized();
-
             let depth = v1.z*w + v2.z*v + v3.z*u;
-            let color = color_a*w +color_b*v + color_c*u;
-
-            let intensity = v1.dot(light).max(0.0);
-            let final_color = Vector3::new(1.0, 1.0, 1.0)*intensity;
-
 
             if w >= 0.0 && v >= 0.0 && u >= 0.0 {
-                fragments.push(Fragment::new(
+                let mut fragment = Fragment::new(
                     x as f32,
                     y as f32,
-                    //Color::new(final_color.x as u8, final_color.y as u8, final_color.z as u8, 255),
-                    final_color,
+                    Vector3::one(), // unlit white; the fragment shader owns all color and lighting
                     depth,
-                ));
+                );
+                fragment.obj_pos = a.obj_pos*w + b.obj_pos*v + c.obj_pos*u;
+                fragment.world_pos = a.world_pos*w + b.world_pos*v + c.world_pos*u;
+                fragment.normal = face_normal.unwrap_or(a.normal*w + b.normal*v + c.normal*u);
+                fragment.uv = a.uv*w + b.uv*v + c.uv*u;
+                fragment.tangent = a.tangent*w + b.tangent*v + c.tangent*u;
+                fragment.bitangent = a.bitangent*w + b.bitangent*v + c.bitangent*u;
+                fragment.uv_dx = uv_dx;
+                fragment.uv_dy = uv_dy;
+                fragment.dp_dx = dp_dx;
+                fragment.dp_dy = dp_dy;
+                if shading == ShadingMode::Gouraud {
+                    fragment.light_diffuse = a.light_diffuse*w + b.light_diffuse*v + c.light_diffuse*u;
+                    fragment.light_specular = a.light_specular*w + b.light_specular*v + c.light_specular*u;
+                }
+                fragments.push(fragment);
             }
         }
     }
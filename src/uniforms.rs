@@ -1,9 +1,15 @@
 // uniforms.rs (si quieres en un archivo aparte) o al inicio de tu shader.rs
 use raylib::prelude::*;
 
+#[derive(Clone, Copy)]
 pub struct Uniforms {
     pub time: f32,         // segundos
     pub resolution: Vector2, // tamaño ventana en píxeles
+    pub camera_pos: Vector3, // posición del ojo en mundo
+    pub light_pos: Vector3,  // estrella que ilumina la escena (mundo)
+    pub light_color: Vector3, // color de cuerpo negro de esa estrella, en luz lineal (0..1)
+    pub light_dir_obj: Vector3, // dirección hacia la estrella en espacio objeto (la fija `render` por entidad)
+    pub model: Matrix,       // matriz de modelo de la entidad que se dibuja (la fija `render`)
 }
 
 // Convierte Color (0..255) a vec3 0..1
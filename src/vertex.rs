@@ -0,0 +1,25 @@
+use raylib::prelude::*;
+
+/// A vertex after the vertex stage: screen position for rasterization plus the
+/// attributes that get interpolated into every fragment of its triangles.
+#[derive(Clone, Copy)]
+pub struct Vertex {
+    pub screen: Vector3,   // x,y in pixels, z = NDC depth
+    pub obj_pos: Vector3,  // object-space position (after displacement)
+    pub world_pos: Vector3,
+    pub normal: Vector3,   // world-space normal (model rotation applied)
+    pub uv: Vector2,       // texture coordinates (zero for meshes without UVs)
+    pub tangent: Vector3,  // world-space +u / +v directions, for tangent-space normal maps
+    pub bitangent: Vector3,
+    pub light_diffuse: Vector3,  // Gouraud shading: star light at the vertex (see `shaders::vertex_lighting`)
+    pub light_specular: Vector3,
+}
+
+/// Per-vertex attributes that go into `render` next to the positions (any slice may be empty)
+#[derive(Clone, Copy, Default)]
+pub struct VertexAttributes<'a> {
+    pub normals: &'a [Vector3],
+    pub uvs: &'a [Vector2],
+    pub tangents: &'a [Vector3],
+    pub bitangents: &'a [Vector3],
+}